@@ -27,6 +27,38 @@ pub enum AppError {
   UdpSocketError { err: io::Error },
   #[error("Deserialization error")]
   DeserializationError { err: serde_json::error::Error },
+  #[error("Invalid ticker pattern `{pattern}`: {reason}")]
+  InvalidTickerPattern { pattern: String, reason: String },
+  #[error("Invalid ticker group definition: `{line}`")]
+  InvalidTickerGroup { line: String },
+  #[error("Invalid session key")]
+  InvalidSessionKey,
+  #[error("Invalid credentials entry for user `{username}`")]
+  InvalidCredentialsEntry { username: String },
+  #[error("TLS error")]
+  TlsError {
+    #[source]
+    err: rustls::Error,
+  },
+  #[error("Failed reading PEM file `{source_path:?}`: {reason}")]
+  PemError {
+    source_path: PathBuf,
+    reason: String,
+  },
+  #[error("Failed sealing datagram")]
+  DatagramSealError,
+  #[error("Invalid sealed datagram")]
+  InvalidSealedDatagram,
+  #[error("Subscription rejected by server: {message}")]
+  SubscriptionRejected { message: String },
+  #[error("Subscription refused by server for now: {message}")]
+  SubscriptionDeferred { message: String },
+  #[error("Protocol error: {message}")]
+  ProtocolError { message: String },
+  #[error("Invalid alert rule `{line}`: {reason}")]
+  InvalidAlertRule { line: String, reason: String },
+  #[error("Invalid recording at line {line}: {reason}")]
+  InvalidRecording { line: usize, reason: String },
   #[error(transparent)]
   OtherError(#[from] anyhow::Error),
 }
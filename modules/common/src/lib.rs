@@ -1,5 +1,8 @@
 //! This is a common crate, which contains structures, types and functions used in workspace crates.
 
 pub mod error;
+pub mod seal;
+pub mod session;
 pub mod stock;
+pub mod tls;
 pub mod utils;
@@ -0,0 +1,203 @@
+use hmac::Mac;
+use ring::aead::{self, Aad, LessSafeKey, Nonce, UnboundKey};
+
+use crate::{error::AppError, session::SessionCredentials};
+
+/// First byte of sealed datagrams, plain `JSON` datagrams start with `{`
+pub const SEALED_DATAGRAM_TAG: u8 = 0x01;
+
+const KEY_LABEL: &[u8] = b"quote datagram key";
+const HEADER_LEN: usize = 1 + size_of::<u64>();
+const REPLAY_WINDOW_LEN: u64 = u64::BITS as u64;
+
+/// # Datagram cipher
+///
+/// Seals quote datagrams with ChaCha20-Poly1305, keyed with HMAC-SHA256 of the
+/// session key, so datagram and heartbeat keys are never shared.
+/// Sealed datagram is `TAG | SEQ | CIPHERTEXT | AUTH TAG`, where big endian
+/// `SEQ` is authenticated as additional data and forms the nonce, so each
+/// sequence number should be sealed once per session.
+///
+/// ```
+/// use common::{seal::DatagramCipher, session::SessionCredentials};
+///
+/// let credentials = SessionCredentials::generate();
+/// let cipher = DatagramCipher::new(&credentials).unwrap();
+///
+/// let mut sealed = cipher.seal(1, br#"{"type":"snapshot"}"#).unwrap();
+/// let (seq, payload) = cipher.open(&sealed).unwrap();
+///
+/// assert_eq!(seq, 1);
+/// assert_eq!(payload, br#"{"type":"snapshot"}"#);
+///
+/// let last = sealed.len() - 1;
+/// sealed[last] ^= 1;
+/// assert!(cipher.open(&sealed).is_err());
+/// ```
+#[derive(Debug)]
+pub struct DatagramCipher {
+  key: LessSafeKey,
+}
+
+impl DatagramCipher {
+  pub fn new(credentials: &SessionCredentials) -> Result<Self, AppError> {
+    let mut mac = credentials.mac()?;
+    mac.update(KEY_LABEL);
+    let key = mac.finalize().into_bytes();
+
+    let key = UnboundKey::new(&aead::CHACHA20_POLY1305, &key)
+      .map_err(|_| AppError::InvalidSessionKey)?;
+
+    Ok(Self {
+      key: LessSafeKey::new(key),
+    })
+  }
+  pub fn seal(&self, seq: u64, payload: &[u8]) -> Result<Vec<u8>, AppError> {
+    let mut sealed = Vec::with_capacity(
+      HEADER_LEN + payload.len() + aead::CHACHA20_POLY1305.tag_len(),
+    );
+    sealed.push(SEALED_DATAGRAM_TAG);
+    sealed.extend_from_slice(&seq.to_be_bytes());
+    sealed.extend_from_slice(payload);
+
+    let (header, in_out) = sealed.split_at_mut(HEADER_LEN);
+    let tag = self
+      .key
+      .seal_in_place_separate_tag(nonce(seq), Aad::from(&*header), in_out)
+      .map_err(|_| AppError::DatagramSealError)?;
+    sealed.extend_from_slice(tag.as_ref());
+
+    Ok(sealed)
+  }
+  /// Authenticated sequence number and payload, replays are checked by caller
+  pub fn open(&self, sealed: &[u8]) -> Result<(u64, Vec<u8>), AppError> {
+    if sealed.len() < HEADER_LEN || sealed[0] != SEALED_DATAGRAM_TAG {
+      return Err(AppError::InvalidSealedDatagram);
+    }
+
+    let (header, ciphertext) = sealed.split_at(HEADER_LEN);
+    let seq = u64::from_be_bytes(
+      header[1..]
+        .try_into()
+        .map_err(|_| AppError::InvalidSealedDatagram)?,
+    );
+
+    let mut in_out = ciphertext.to_vec();
+    let payload_len = self
+      .key
+      .open_in_place(nonce(seq), Aad::from(header), &mut in_out)
+      .map_err(|_| AppError::InvalidSealedDatagram)?
+      .len();
+    in_out.truncate(payload_len);
+
+    Ok((seq, in_out))
+  }
+}
+
+fn nonce(seq: u64) -> Nonce {
+  let mut nonce = [0u8; aead::NONCE_LEN];
+  nonce[aead::NONCE_LEN - size_of::<u64>()..]
+    .copy_from_slice(&seq.to_be_bytes());
+
+  Nonce::assume_unique_for_key(nonce)
+}
+
+/// # Replay window
+///
+/// Sliding window of the latest 64 sequence numbers, as in DTLS. Each number
+/// is accepted once, numbers older than the window are rejected, so reordered
+/// datagrams are accepted and replayed ones are not.
+///
+/// ```
+/// use common::seal::ReplayWindow;
+///
+/// let mut window = ReplayWindow::default();
+///
+/// assert!(window.accept(2));
+/// assert!(window.accept(1));
+/// assert!(!window.accept(2));
+/// assert!(window.accept(100));
+/// assert!(!window.accept(3));
+/// ```
+#[derive(Debug, Default)]
+pub struct ReplayWindow {
+  latest: u64,
+  // Bit `n` marks sequence number `latest - n` as accepted
+  bitmap: u64,
+}
+
+impl ReplayWindow {
+  pub fn accept(&mut self, seq: u64) -> bool {
+    if seq > self.latest {
+      let shift = seq - self.latest;
+      self.bitmap = if shift < REPLAY_WINDOW_LEN {
+        self.bitmap << shift
+      } else {
+        0
+      };
+      self.bitmap |= 1;
+      self.latest = seq;
+
+      return true;
+    }
+
+    let offset = self.latest - seq;
+    if offset >= REPLAY_WINDOW_LEN || self.bitmap & (1 << offset) != 0 {
+      return false;
+    }
+    self.bitmap |= 1 << offset;
+
+    true
+  }
+}
+
+#[cfg(test)]
+mod tests {
+  use super::*;
+
+  #[test]
+  fn replay_window_slides() {
+    let mut window = ReplayWindow::default();
+
+    assert!(window.accept(100));
+    // Oldest number within the window and the first one out of it
+    assert!(window.accept(100 - (REPLAY_WINDOW_LEN - 1)));
+    assert!(!window.accept(100 - REPLAY_WINDOW_LEN));
+    assert!(!window.accept(100 - (REPLAY_WINDOW_LEN - 1)));
+
+    // Jumps over the window length forget accepted numbers
+    assert!(window.accept(100 + REPLAY_WINDOW_LEN * 2));
+    assert!(window.accept(100 + REPLAY_WINDOW_LEN + 1));
+    assert!(!window.accept(100));
+  }
+
+  #[test]
+  fn replay_window_accepts_highest_seq() {
+    let mut window = ReplayWindow::default();
+
+    assert!(window.accept(u64::MAX - 1));
+    assert!(window.accept(u64::MAX));
+    assert!(!window.accept(u64::MAX));
+    assert!(!window.accept(u64::MAX - 1));
+  }
+
+  #[test]
+  fn sealed_datagrams_are_authenticated() {
+    let credentials = SessionCredentials::generate();
+    let cipher = DatagramCipher::new(&credentials).unwrap();
+    let sealed = cipher.seal(7, b"payload").unwrap();
+
+    assert_eq!(cipher.open(&sealed).unwrap(), (7, b"payload".to_vec()));
+
+    // Sequence number is authenticated
+    let mut tampered = sealed.clone();
+    tampered[HEADER_LEN - 1] ^= 1;
+    assert!(cipher.open(&tampered).is_err());
+
+    // Other sessions can't open it
+    let other = DatagramCipher::new(&SessionCredentials::generate()).unwrap();
+    assert!(other.open(&sealed).is_err());
+
+    assert!(cipher.open(&sealed[..HEADER_LEN - 1]).is_err());
+  }
+}
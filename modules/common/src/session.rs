@@ -0,0 +1,159 @@
+use std::time::Duration;
+
+use hmac::{Hmac, Mac};
+use sha2::Sha256;
+
+use crate::error::AppError;
+
+type HmacSha256 = Hmac<Sha256>;
+
+const SESSION_ID_LEN: usize = 16;
+const SESSION_KEY_LEN: usize = 32;
+const NONCE_LEN: usize = 16;
+
+/// Hex encoded random nonce
+pub fn generate_nonce() -> String {
+  hex::encode(rand::random::<[u8; NONCE_LEN]>())
+}
+
+/// # ClientDatagram sent by client through UDP socket
+///
+/// ```
+/// use common::session::{ClientDatagram, Heartbeat, SessionCredentials};
+///
+/// let credentials = SessionCredentials::generate();
+/// let datagram =
+///   ClientDatagram::Heartbeat(Heartbeat::sign(&credentials, 1).unwrap());
+///
+/// let message = serde_json::to_vec(&datagram).unwrap();
+///
+/// assert!(matches!(
+///   serde_json::from_slice::<ClientDatagram>(&message).unwrap(),
+///   ClientDatagram::Heartbeat(_)
+/// ));
+/// ```
+#[derive(Debug, Clone, serde::Serialize, serde::Deserialize)]
+#[serde(tag = "type", rename_all = "snake_case")]
+pub enum ClientDatagram {
+  Heartbeat(Heartbeat),
+  /// Echo of the server challenge nonce, which proves that client receives
+  /// datagrams on the subscribed address
+  Verification {
+    nonce: String,
+  },
+}
+
+/// Per-session credentials issued by server in `STREAM` response
+///
+/// Both values are hex encoded random bytes, `key` signs client heartbeats
+/// and derives the key of sealed quote datagrams.
+#[derive(Debug, Clone, serde::Serialize, serde::Deserialize)]
+pub struct SessionCredentials {
+  pub id: String,
+  pub key: String,
+}
+
+impl SessionCredentials {
+  pub fn generate() -> Self {
+    Self {
+      id: hex::encode(rand::random::<[u8; SESSION_ID_LEN]>()),
+      key: hex::encode(rand::random::<[u8; SESSION_KEY_LEN]>()),
+    }
+  }
+  pub(crate) fn mac(&self) -> Result<HmacSha256, AppError> {
+    let key =
+      hex::decode(&self.key).map_err(|_| AppError::InvalidSessionKey)?;
+
+    HmacSha256::new_from_slice(&key).map_err(|_| AppError::InvalidSessionKey)
+  }
+}
+
+/// Heartbeat terms agreed in `STREAM` handshake
+///
+/// Server clamps the intervals proposed by client to its limits. Client sends
+/// heartbeats every `interval_ms`, server evicts the subscription when no
+/// heartbeat arrives within `timeout_ms`.
+///
+/// ```
+/// use std::time::Duration;
+/// use common::session::HeartbeatPolicy;
+///
+/// let policy = HeartbeatPolicy {
+///   interval_ms: 1000,
+///   timeout_ms: 5000,
+/// };
+///
+/// assert_eq!(policy.interval(), Duration::from_secs(1));
+/// assert_eq!(policy.timeout(), Duration::from_secs(5));
+/// ```
+#[derive(Debug, Clone, Copy, serde::Serialize, serde::Deserialize)]
+pub struct HeartbeatPolicy {
+  pub interval_ms: u64,
+  pub timeout_ms: u64,
+}
+
+impl HeartbeatPolicy {
+  pub fn interval(&self) -> Duration {
+    Duration::from_millis(self.interval_ms)
+  }
+  pub fn timeout(&self) -> Duration {
+    Duration::from_millis(self.timeout_ms)
+  }
+}
+
+/// # Heartbeat sent by client through UDP socket
+///
+/// Heartbeat is signed with HMAC-SHA256 of session id and `counter`, server
+/// accepts heartbeats with valid signature and `counter` greater than the last
+/// accepted one, so captured heartbeats can't be replayed.
+///
+/// ```
+/// use common::session::{Heartbeat, SessionCredentials};
+///
+/// let credentials = SessionCredentials::generate();
+/// let heartbeat = Heartbeat::sign(&credentials, 1).unwrap();
+///
+/// assert!(heartbeat.verify(&credentials));
+///
+/// let forged = Heartbeat { counter: 2, ..heartbeat };
+/// assert!(!forged.verify(&credentials));
+/// ```
+#[derive(Debug, Clone, serde::Serialize, serde::Deserialize)]
+pub struct Heartbeat {
+  pub session: String,
+  pub counter: u64,
+  pub mac: String,
+}
+
+impl Heartbeat {
+  pub fn sign(
+    credentials: &SessionCredentials,
+    counter: u64,
+  ) -> Result<Self, AppError> {
+    let mut mac = credentials.mac()?;
+    mac.update(credentials.id.as_bytes());
+    mac.update(&counter.to_be_bytes());
+
+    Ok(Self {
+      session: credentials.id.clone(),
+      counter,
+      mac: hex::encode(mac.finalize().into_bytes()),
+    })
+  }
+  /// Check signature in constant time, replays are checked by caller
+  pub fn verify(&self, credentials: &SessionCredentials) -> bool {
+    let (Ok(mut mac), Ok(signature)) =
+      (credentials.mac(), hex::decode(&self.mac))
+    else {
+      return false;
+    };
+
+    if self.session != credentials.id {
+      return false;
+    }
+
+    mac.update(self.session.as_bytes());
+    mac.update(&self.counter.to_be_bytes());
+    mac.verify_slice(&signature).is_ok()
+  }
+}
@@ -1,13 +1,83 @@
-use std::net::SocketAddr;
+use std::{
+  collections::HashMap, fmt, net::SocketAddr, str::FromStr, time::Duration,
+};
 
 use serde;
 
-#[derive(Debug, Clone, serde::Serialize, serde::Deserialize)]
+use crate::session::{HeartbeatPolicy, SessionCredentials};
+
+/// # StockQuote
+///
+/// Server generates complete quotes, subscriptions with field projection
+/// receive the `ticker` and requested fields only, other fields are omitted
+/// from serialized data.
+#[derive(Debug, Clone, PartialEq, serde::Serialize, serde::Deserialize)]
 pub struct StockQuote {
   pub ticker: String,
-  pub price: f64,
-  pub volume: u32,
-  pub timestamp: u64,
+  #[serde(default, skip_serializing_if = "Option::is_none")]
+  pub price: Option<f64>,
+  #[serde(default, skip_serializing_if = "Option::is_none")]
+  pub volume: Option<u32>,
+  #[serde(default, skip_serializing_if = "Option::is_none")]
+  pub timestamp: Option<u64>,
+}
+
+impl StockQuote {
+  /// Keep the `ticker` and listed fields only
+  ///
+  /// # Example
+  ///
+  /// ```
+  /// use common::stock::{QuoteField, StockQuote};
+  ///
+  /// let mut quote = StockQuote {
+  ///   ticker: "AAPL".to_string(),
+  ///   price: Some(1.5),
+  ///   volume: Some(100),
+  ///   timestamp: Some(0),
+  /// };
+  /// quote.project(&[QuoteField::Price]);
+  ///
+  /// assert_eq!(
+  ///   serde_json::to_string(&quote).unwrap(),
+  ///   r#"{"ticker":"AAPL","price":1.5}"#
+  /// );
+  /// ```
+  pub fn project(&mut self, fields: &[QuoteField]) {
+    if !fields.contains(&QuoteField::Price) {
+      self.price = None;
+    }
+    if !fields.contains(&QuoteField::Volume) {
+      self.volume = None;
+    }
+    if !fields.contains(&QuoteField::Timestamp) {
+      self.timestamp = None;
+    }
+  }
+}
+
+/// Optional `StockQuote` fields, `ticker` is always included
+#[derive(
+  Debug, Clone, Copy, PartialEq, Eq, serde::Serialize, serde::Deserialize,
+)]
+#[serde(rename_all = "snake_case")]
+pub enum QuoteField {
+  Price,
+  Volume,
+  Timestamp,
+}
+
+impl FromStr for QuoteField {
+  type Err = anyhow::Error;
+
+  fn from_str(str: &str) -> Result<Self, Self::Err> {
+    match str.trim().to_lowercase().as_str() {
+      "price" => Ok(Self::Price),
+      "volume" => Ok(Self::Volume),
+      "timestamp" => Ok(Self::Timestamp),
+      _ => anyhow::bail!("Unknown quote field `{str}`"),
+    }
+  }
 }
 
 /// # StockRequest serialization and deserialization
@@ -15,7 +85,7 @@ pub struct StockQuote {
 /// # Serialization
 /// ```
 /// use std::io::Write;
-/// use common::{stock::{StockRequest}};
+/// use common::{stock::{StockRequest, SubscriptionOptions}};
 /// use anyhow::{Result, Context};
 /// use serde_json::json;
 ///
@@ -25,6 +95,8 @@ pub struct StockQuote {
 ///     kind: "STREAM".to_string(),
 ///     addr: "127.0.0.1:8080".parse()?,
 ///     tickers: vec![],
+///     options: SubscriptionOptions::default(),
+///     auth: None,
 ///   };
 ///
 ///   let message = json!(stock_request).to_string();
@@ -51,6 +123,8 @@ pub struct StockQuote {
 ///     kind,
 ///     addr,
 ///     tickers,
+///     options,
+///     auth,
 ///   } = serde_json::from_slice::<StockRequest>(&buf[..n])?;
 ///
 ///   Ok(())
@@ -61,16 +135,232 @@ pub struct StockRequest {
   pub kind: String,
   pub addr: SocketAddr,
   pub tickers: Vec<String>,
+  #[serde(default)]
+  pub options: SubscriptionOptions,
+  #[serde(default, skip_serializing_if = "Option::is_none")]
+  pub auth: Option<Credentials>,
+}
+
+/// Username and password checked by server when authentication is enabled
+#[derive(Clone, serde::Serialize, serde::Deserialize)]
+pub struct Credentials {
+  pub username: String,
+  pub password: String,
+}
+
+impl fmt::Debug for Credentials {
+  fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+    f.debug_struct("Credentials")
+      .field("username", &self.username)
+      .field("password", &"***")
+      .finish()
+  }
+}
+
+#[derive(Debug, Default, Clone, serde::Serialize, serde::Deserialize)]
+pub struct SubscriptionOptions {
+  /// Fail the subscription when any requested ticker is unknown to server
+  #[serde(default)]
+  pub strict: bool,
+  /// Minimum interval between sends in milliseconds
+  #[serde(default, skip_serializing_if = "Option::is_none")]
+  pub min_interval_ms: Option<u64>,
+  /// Maximum sends per second
+  #[serde(default, skip_serializing_if = "Option::is_none")]
+  pub max_rate: Option<f64>,
+  /// Send only quotes changed since the previous send
+  #[serde(default)]
+  pub delta: bool,
+  /// Interval between full snapshots in delta mode in milliseconds
+  #[serde(default, skip_serializing_if = "Option::is_none")]
+  pub full_refresh_ms: Option<u64>,
+  /// Quote fields to send, all fields when not set
+  #[serde(default, skip_serializing_if = "Option::is_none")]
+  pub fields: Option<Vec<QuoteField>>,
+  /// Seal quote datagrams with the session datagram key
+  #[serde(default)]
+  pub encrypt: bool,
+  /// Proposed client heartbeat interval in milliseconds
+  #[serde(default, skip_serializing_if = "Option::is_none")]
+  pub heartbeat_interval_ms: Option<u64>,
+  /// Proposed eviction timeout without heartbeats in milliseconds
+  #[serde(default, skip_serializing_if = "Option::is_none")]
+  pub heartbeat_timeout_ms: Option<u64>,
+}
+
+impl SubscriptionOptions {
+  /// Minimum interval between sends, which satisfies both the interval and
+  /// the rate limits. Quotes are conflated to the latest per ticker between
+  /// sends.
+  ///
+  /// # Example
+  ///
+  /// ```
+  /// use std::time::Duration;
+  /// use common::stock::SubscriptionOptions;
+  ///
+  /// let options = SubscriptionOptions {
+  ///   min_interval_ms: Some(500),
+  ///   max_rate: Some(1.0),
+  ///   ..SubscriptionOptions::default()
+  /// };
+  ///
+  /// assert_eq!(options.send_interval(), Some(Duration::from_secs(1)));
+  /// assert_eq!(SubscriptionOptions::default().send_interval(), None);
+  /// ```
+  pub fn send_interval(&self) -> Option<Duration> {
+    let interval = self.min_interval_ms.map(Duration::from_millis);
+    let rate_interval = self
+      .max_rate
+      .filter(|rate| rate.is_finite() && *rate > 0.0)
+      .map(|rate| Duration::from_secs_f64(1.0 / rate));
+
+    interval
+      .max(rate_interval)
+      .filter(|interval| !interval.is_zero())
+  }
+}
+
+/// # StockDatagram sent by server through UDP socket
+///
+/// Each subscription numbers its quotes datagrams with `seq`, so a gap means
+/// lost data. In delta mode a lost `Delta` leaves stale quotes until the next
+/// refresh `Snapshot`. Quotes are sent after client echoes the `Challenge` nonce.
+/// Quotes datagrams carry `sent_at` time, so latency of the server and the
+/// network are measured apart.
+/// `Heartbeat` is sent when no quotes were sent within the heartbeat interval,
+/// so quiet market is distinguished from dead server. `GoingAway` is the last
+/// datagram of a subscription ended by server shutdown.
+///
+/// ```
+/// use common::stock::{StockDatagram, StockQuote};
+///
+/// let datagram = StockDatagram::Delta {
+///   seq: 2,
+///   sent_at: Some(1),
+///   quotes: vec![StockQuote {
+///     ticker: "AAPL".to_string(),
+///     price: Some(1.5),
+///     volume: Some(100),
+///     timestamp: Some(0),
+///   }],
+/// };
+///
+/// let message = serde_json::to_vec(&datagram).unwrap();
+/// let datagram = serde_json::from_slice::<StockDatagram>(&message).unwrap();
+///
+/// assert_eq!(datagram.seq(), Some(2));
+/// assert_eq!(datagram.quotes()[0].ticker, "AAPL");
+/// ```
+#[derive(Debug, Clone, serde::Serialize, serde::Deserialize)]
+#[serde(tag = "type", rename_all = "snake_case")]
+pub enum StockDatagram {
+  /// Latest quotes of every subscribed ticker
+  Snapshot {
+    seq: u64,
+    /// Send time in milliseconds since Unix epoch
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    sent_at: Option<u64>,
+    /// Periodic resend of quotes already sent in delta mode, only quotes
+    /// changed since the previous send are new
+    #[serde(default, skip_serializing_if = "std::ops::Not::not")]
+    refresh: bool,
+    quotes: Vec<StockQuote>,
+  },
+  /// Quotes changed since the previous send
+  Delta {
+    seq: u64,
+    /// Send time in milliseconds since Unix epoch
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    sent_at: Option<u64>,
+    quotes: Vec<StockQuote>,
+  },
+  /// Subscribed address ownership check
+  Challenge { nonce: String },
+  /// Server liveness notice on idle subscription
+  Heartbeat { seq: u64 },
+  /// Server shutdown notice, no datagrams follow
+  GoingAway { seq: u64, message: String },
+}
+
+impl StockDatagram {
+  pub fn seq(&self) -> Option<u64> {
+    match self {
+      Self::Snapshot { seq, .. }
+      | Self::Delta { seq, .. }
+      | Self::Heartbeat { seq }
+      | Self::GoingAway { seq, .. } => Some(*seq),
+      Self::Challenge { .. } => None,
+    }
+  }
+  pub fn sent_at(&self) -> Option<u64> {
+    match self {
+      Self::Snapshot { sent_at, .. } | Self::Delta { sent_at, .. } => *sent_at,
+      Self::Challenge { .. }
+      | Self::Heartbeat { .. }
+      | Self::GoingAway { .. } => None,
+    }
+  }
+  pub fn quotes(&self) -> &[StockQuote] {
+    match self {
+      Self::Snapshot { quotes, .. } | Self::Delta { quotes, .. } => quotes,
+      Self::Challenge { .. }
+      | Self::Heartbeat { .. }
+      | Self::GoingAway { .. } => &[],
+    }
+  }
 }
 
 #[derive(Debug, serde::Serialize, serde::Deserialize)]
 pub enum StockResponseStatus {
   Ok,
   Error,
+  /// Refused by server limits, the same request may succeed later
+  Retry,
 }
 
 #[derive(Debug, serde::Serialize, serde::Deserialize)]
 pub struct StockResponse {
   pub status: StockResponseStatus,
   pub message: String,
+  #[serde(default, skip_serializing_if = "Option::is_none")]
+  pub report: Option<SubscriptionReport>,
+  /// Credentials for signing heartbeats of accepted subscription
+  #[serde(default, skip_serializing_if = "Option::is_none")]
+  pub session: Option<SessionCredentials>,
+  /// Heartbeat terms of accepted subscription
+  #[serde(default, skip_serializing_if = "Option::is_none")]
+  pub heartbeat: Option<HeartbeatPolicy>,
+}
+
+impl StockResponse {
+  pub fn error(message: impl Into<String>) -> Self {
+    Self {
+      status: StockResponseStatus::Error,
+      message: message.into(),
+      report: None,
+      session: None,
+      heartbeat: None,
+    }
+  }
+  pub fn retry(message: impl Into<String>) -> Self {
+    Self {
+      status: StockResponseStatus::Retry,
+      ..Self::error(message)
+    }
+  }
+}
+
+/// Resolution of requested tickers on subscription
+#[derive(Debug, Default, serde::Serialize, serde::Deserialize)]
+pub struct SubscriptionReport {
+  /// Server tickers matched by requested patterns
+  pub accepted: Vec<String>,
+  /// Requested patterns which matched no server tickers
+  pub unknown: Vec<String>,
+  /// Closest server tickers for each unknown pattern
+  pub suggestions: HashMap<String, Vec<String>>,
+  /// Matched tickers which the user is not entitled to
+  #[serde(default, skip_serializing_if = "Vec::is_empty")]
+  pub denied: Vec<String>,
 }
@@ -0,0 +1,151 @@
+use std::{
+  io::{Read, Write},
+  net::{IpAddr, TcpStream},
+  path::Path,
+  sync::Arc,
+};
+
+use rustls::{
+  ClientConfig, ClientConnection, RootCertStore, ServerConfig,
+  ServerConnection, StreamOwned, server::WebPkiClientVerifier,
+};
+use rustls_pki_types::{
+  CertificateDer, PrivateKeyDer, ServerName, pem::PemObject,
+};
+
+use crate::error::AppError;
+
+/// Bidirectional control channel stream, plain TCP or TLS over TCP
+pub trait ControlStream: Read + Write + Send {}
+
+impl<T: Read + Write + Send> ControlStream for T {}
+
+/// Server side TLS settings of the control channel
+///
+/// Client certificates are required and verified with `client_ca` when set.
+pub fn server_config(
+  cert: &Path,
+  key: &Path,
+  client_ca: Option<&Path>,
+) -> Result<Arc<ServerConfig>, AppError> {
+  let builder = ServerConfig::builder_with_provider(Arc::new(
+    rustls::crypto::ring::default_provider(),
+  ))
+  .with_safe_default_protocol_versions()
+  .map_err(|err| AppError::TlsError { err })?;
+
+  let builder = match client_ca {
+    Some(client_ca) => {
+      let verifier = WebPkiClientVerifier::builder_with_provider(
+        Arc::new(read_root_store(client_ca)?),
+        Arc::new(rustls::crypto::ring::default_provider()),
+      )
+      .build()
+      .map_err(|err| AppError::TlsError {
+        err: rustls::Error::General(err.to_string()),
+      })?;
+
+      builder.with_client_cert_verifier(verifier)
+    }
+    None => builder.with_no_client_auth(),
+  };
+
+  let config = builder
+    .with_single_cert(read_certs(cert)?, read_key(key)?)
+    .map_err(|err| AppError::TlsError { err })?;
+
+  Ok(Arc::new(config))
+}
+
+/// Client side TLS settings of the control channel
+///
+/// Server certificate is verified with `ca`, client certificate is sent when
+/// `identity` certificate and key paths are set.
+pub fn client_config(
+  ca: &Path,
+  identity: Option<(&Path, &Path)>,
+) -> Result<Arc<ClientConfig>, AppError> {
+  let builder = ClientConfig::builder_with_provider(Arc::new(
+    rustls::crypto::ring::default_provider(),
+  ))
+  .with_safe_default_protocol_versions()
+  .map_err(|err| AppError::TlsError { err })?
+  .with_root_certificates(read_root_store(ca)?);
+
+  let config = match identity {
+    Some((cert, key)) => builder
+      .with_client_auth_cert(read_certs(cert)?, read_key(key)?)
+      .map_err(|err| AppError::TlsError { err })?,
+    None => builder.with_no_client_auth(),
+  };
+
+  Ok(Arc::new(config))
+}
+
+/// Wrap accepted TCP stream with TLS when server config is set
+pub fn accept<S: ControlStream + 'static>(
+  stream: S,
+  config: Option<&Arc<ServerConfig>>,
+) -> Result<Box<dyn ControlStream>, AppError> {
+  let Some(config) = config else {
+    return Ok(Box::new(stream));
+  };
+
+  let connection = ServerConnection::new(Arc::clone(config))
+    .map_err(|err| AppError::TlsError { err })?;
+
+  Ok(Box::new(StreamOwned::new(connection, stream)))
+}
+
+/// Wrap connected TCP stream with TLS when client config is set
+///
+/// Server certificate should be issued for `server_name`, which may be a DNS
+/// name or an IP address.
+pub fn connect(
+  stream: TcpStream,
+  config: Option<(&Arc<ClientConfig>, &str)>,
+) -> Result<Box<dyn ControlStream>, AppError> {
+  let Some((config, server_name)) = config else {
+    return Ok(Box::new(stream));
+  };
+
+  let server_name = match server_name.parse::<IpAddr>() {
+    Ok(ip) => ServerName::IpAddress(ip.into()),
+    Err(_) => ServerName::try_from(server_name.to_string()).map_err(|_| {
+      AppError::TlsError {
+        err: rustls::Error::General(format!(
+          "Invalid server name `{server_name}`"
+        )),
+      }
+    })?,
+  };
+  let connection = ClientConnection::new(Arc::clone(config), server_name)
+    .map_err(|err| AppError::TlsError { err })?;
+
+  Ok(Box::new(StreamOwned::new(connection, stream)))
+}
+
+fn read_certs(path: &Path) -> Result<Vec<CertificateDer<'static>>, AppError> {
+  CertificateDer::pem_file_iter(path)
+    .and_then(|certs| certs.collect::<Result<Vec<_>, _>>())
+    .map_err(|err| AppError::PemError {
+      source_path: path.to_path_buf(),
+      reason: err.to_string(),
+    })
+}
+
+fn read_key(path: &Path) -> Result<PrivateKeyDer<'static>, AppError> {
+  PrivateKeyDer::from_pem_file(path).map_err(|err| AppError::PemError {
+    source_path: path.to_path_buf(),
+    reason: err.to_string(),
+  })
+}
+
+fn read_root_store(path: &Path) -> Result<RootCertStore, AppError> {
+  let mut store = RootCertStore::empty();
+  for cert in read_certs(path)? {
+    store.add(cert).map_err(|err| AppError::TlsError { err })?;
+  }
+
+  Ok(store)
+}
@@ -1,17 +1,20 @@
 use std::{
+  collections::HashMap,
   ffi::OsStr,
   fs::File,
   io,
   io::ErrorKind,
-  io::{BufRead, BufReader},
+  io::{BufRead, BufReader, Read},
   net::SocketAddr,
   path::PathBuf,
   str::FromStr,
   sync::Arc,
   sync::atomic::AtomicBool,
+  time::{Duration, SystemTime, UNIX_EPOCH},
 };
 
 use anyhow::Context;
+use serde::de::DeserializeOwned;
 use signal_hook::{consts::TERM_SIGNALS, flag};
 
 use crate::error::AppError;
@@ -34,6 +37,8 @@ pub fn read_tickers(path: PathBuf) -> Result<Vec<String>, AppError> {
   let tickers_file = File::open(path).context("Failed reading tickers file")?;
   let reader = BufReader::new(tickers_file);
   let lines = reader.lines();
+  // Unreadable lines are skipped
+  #[allow(clippy::lines_filter_map_ok)]
   let tickers: Vec<String> = lines
     .filter_map(anyhow::Result::ok)
     .map(|str| str.trim().to_string())
@@ -42,22 +47,116 @@ pub fn read_tickers(path: PathBuf) -> Result<Vec<String>, AppError> {
   Ok(tickers)
 }
 
+/// Read named ticker groups from a file
+///
+/// Each line defines a group as `NAME: TICKER, TICKER, ...`, members are kept
+/// as written and may contain ticker patterns. Empty lines and lines starting
+/// with `#` are skipped.
+///
+/// # Example
+///
+/// ```
+/// use common::{ utils::{read_ticker_groups}, error::AppError };
+/// use std::{path::PathBuf};
+///
+/// fn main() -> Result<(), AppError>{
+///   let groups = read_ticker_groups(PathBuf::from("../../mocks/server-groups.txt"))?;
+///
+///   assert!(groups["TECH"].contains(&"AAPL".to_string()));
+///
+///   Ok(())
+/// }
+/// ```
+pub fn read_ticker_groups(
+  path: PathBuf,
+) -> Result<HashMap<String, Vec<String>>, AppError> {
+  let groups_file = File::open(path).context("Failed reading groups file")?;
+  let reader = BufReader::new(groups_file);
+  let mut groups: HashMap<String, Vec<String>> = HashMap::new();
+
+  for line in reader.lines() {
+    let line = line.context("Failed reading groups file")?;
+    let line = line.trim();
+    if line.is_empty() || line.starts_with('#') {
+      continue;
+    }
+
+    let Some((name, members)) = line.split_once(':') else {
+      return Err(AppError::InvalidTickerGroup {
+        line: line.to_string(),
+      });
+    };
+
+    groups.insert(
+      name.trim().to_string(),
+      members
+        .split(',')
+        .map(str::trim)
+        .filter(|member| !member.is_empty())
+        .map(str::to_string)
+        .collect(),
+    );
+  }
+
+  Ok(groups)
+}
+
 pub fn register_signal_hooks(
   shutdown: &Arc<AtomicBool>,
 ) -> Result<(), AppError> {
   for sig in TERM_SIGNALS {
-    flag::register_conditional_shutdown(*sig, 1, Arc::clone(&shutdown))
+    flag::register_conditional_shutdown(*sig, 1, Arc::clone(shutdown))
       .map_err(|err| AppError::SignalError { err, signal: *sig })?;
-    flag::register(*sig, Arc::clone(&shutdown))
+    flag::register(*sig, Arc::clone(shutdown))
       .map_err(|err| AppError::SignalError { err, signal: *sig })?;
   }
 
   Ok(())
 }
 
+/// Read a single JSON value from a stream
+///
+/// Reading stops right after the value ends, so the stream may be reused for
+/// following messages and the value size is not limited by a read buffer.
+///
+/// # Example
+///
+/// ```
+/// use common::{ utils::{read_json}, stock::{StockRequest}, error::AppError };
+///
+/// fn main() -> Result<(), AppError>{
+///   let message = br#"{"kind":"STREAM","addr":"127.0.0.1:8002","tickers":["*"]}"#;
+///   let request: StockRequest = read_json(&message[..])?;
+///
+///   assert_eq!(request.tickers, vec!["*"]);
+///
+///   Ok(())
+/// }
+/// ```
+pub fn read_json<T: DeserializeOwned>(
+  reader: impl Read,
+) -> Result<T, AppError> {
+  let mut deserializer = serde_json::Deserializer::from_reader(reader);
+
+  T::deserialize(&mut deserializer)
+    .map_err(|err| AppError::DeserializationError { err })
+}
+
 pub(crate) const EXTENSION_WHITELIST: &[&str] = &["txt"];
+pub(crate) const PEM_EXTENSION_WHITELIST: &[&str] = &["pem", "crt", "key"];
 
 pub fn path_validation(str: &str) -> Result<PathBuf, AppError> {
+  extension_path_validation(str, EXTENSION_WHITELIST)
+}
+
+pub fn pem_path_validation(str: &str) -> Result<PathBuf, AppError> {
+  extension_path_validation(str, PEM_EXTENSION_WHITELIST)
+}
+
+fn extension_path_validation(
+  str: &str,
+  whitelist: &[&str],
+) -> Result<PathBuf, AppError> {
   let path =
     PathBuf::from_str(str).expect("Failed reading provided path value");
 
@@ -72,7 +171,7 @@ pub fn path_validation(str: &str) -> Result<PathBuf, AppError> {
   }
 
   if let Some(extension) = path.extension().and_then(OsStr::to_str) {
-    if EXTENSION_WHITELIST.contains(&extension) {
+    if whitelist.contains(&extension) {
       return Ok(path);
     }
   }
@@ -94,3 +193,48 @@ pub fn port_validation(str: &str) -> anyhow::Result<u16> {
 
   Ok(port)
 }
+
+pub fn rate_validation(str: &str) -> anyhow::Result<f64> {
+  let rate = str.parse::<f64>()?;
+
+  if !rate.is_finite() || rate <= 0.0 {
+    anyhow::bail!("Rate should be a positive number");
+  }
+
+  Ok(rate)
+}
+
+pub fn percent_validation(str: &str) -> anyhow::Result<f64> {
+  let percent = str.parse::<f64>()?;
+
+  if !percent.is_finite() || percent < 0.0 {
+    anyhow::bail!("Percent should be a non-negative number");
+  }
+
+  Ok(percent)
+}
+
+/// Time window as `30s`, `5m` or `1h`
+pub fn window_validation(str: &str) -> anyhow::Result<Duration> {
+  let (amount, unit_secs) = [("s", 1), ("m", 60), ("h", 60 * 60)]
+    .into_iter()
+    .find_map(|(unit, secs)| {
+      str.strip_suffix(unit).map(|amount| (amount, secs))
+    })
+    .context(format!("Invalid window `{str}`, expected `s`, `m` or `h`"))?;
+  let amount = amount
+    .parse::<u64>()
+    .ok()
+    .filter(|amount| *amount > 0)
+    .context(format!("Invalid window `{str}`"))?;
+
+  Ok(Duration::from_secs(amount * unit_secs))
+}
+
+/// Current time in milliseconds since Unix epoch
+pub fn unix_millis() -> u64 {
+  SystemTime::now()
+    .duration_since(UNIX_EPOCH)
+    .unwrap_or_default()
+    .as_millis() as u64
+}
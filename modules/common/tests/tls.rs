@@ -0,0 +1,165 @@
+use std::{
+  fs,
+  io::{BufReader, Write},
+  net::{TcpListener, TcpStream},
+  path::{Path, PathBuf},
+  process, thread,
+};
+
+use rcgen::{
+  BasicConstraints, CertificateParams, ExtendedKeyUsagePurpose, IsCa, KeyPair,
+};
+use serde_json::json;
+
+use common::{
+  error::AppError,
+  stock::{StockRequest, StockResponse, StockResponseStatus},
+  tls,
+  utils::read_json,
+};
+
+/// Locally generated CA with server and client certificates in PEM files
+struct TestPki {
+  dir: PathBuf,
+}
+
+impl TestPki {
+  fn generate(name: &str) -> Self {
+    let dir =
+      std::env::temp_dir().join(format!("stocks-tls-{}-{name}", process::id()));
+    fs::create_dir_all(&dir).unwrap();
+
+    let mut ca_params = CertificateParams::new(Vec::<String>::new()).unwrap();
+    ca_params.is_ca = IsCa::Ca(BasicConstraints::Unconstrained);
+    let ca_key = KeyPair::generate().unwrap();
+    let ca_cert = ca_params.self_signed(&ca_key).unwrap();
+    fs::write(dir.join("ca.pem"), ca_cert.pem()).unwrap();
+
+    for (entity, purpose) in [
+      ("server", ExtendedKeyUsagePurpose::ServerAuth),
+      ("client", ExtendedKeyUsagePurpose::ClientAuth),
+    ] {
+      let mut params =
+        CertificateParams::new(vec!["localhost".into(), "127.0.0.1".into()])
+          .unwrap();
+      params.extended_key_usages = vec![purpose];
+      let key = KeyPair::generate().unwrap();
+      let cert = params.signed_by(&key, &ca_cert, &ca_key).unwrap();
+
+      fs::write(dir.join(format!("{entity}.pem")), cert.pem()).unwrap();
+      fs::write(dir.join(format!("{entity}.key")), key.serialize_pem())
+        .unwrap();
+    }
+
+    Self { dir }
+  }
+  fn path(&self, file: &str) -> PathBuf {
+    self.dir.join(file)
+  }
+}
+
+impl Drop for TestPki {
+  fn drop(&mut self) {
+    let _ = fs::remove_dir_all(&self.dir);
+  }
+}
+
+/// Serve a single `STREAM` request over TLS and answer with `ok`
+fn spawn_server(
+  pki: &TestPki,
+  client_ca: Option<&Path>,
+) -> (u16, thread::JoinHandle<Result<StockRequest, AppError>>) {
+  let config = tls::server_config(
+    &pki.path("server.pem"),
+    &pki.path("server.key"),
+    client_ca,
+  )
+  .unwrap();
+  let listener = TcpListener::bind("127.0.0.1:0").unwrap();
+  let port = listener.local_addr().unwrap().port();
+
+  let handle = thread::spawn(move || {
+    let (stream, _) = listener.accept()?;
+    let mut stream = tls::accept(stream, Some(&config))?;
+
+    let request = read_json::<StockRequest>(BufReader::new(&mut stream))?;
+    let response = StockResponse {
+      status: StockResponseStatus::Ok,
+      message: "ok".to_string(),
+      report: None,
+      session: None,
+      heartbeat: None,
+    };
+    stream.write_all(json!(response).to_string().as_bytes())?;
+    stream.flush()?;
+
+    Ok(request)
+  });
+
+  (port, handle)
+}
+
+fn send_request(
+  port: u16,
+  ca: &Path,
+  identity: Option<(&Path, &Path)>,
+) -> Result<StockResponse, AppError> {
+  let config = tls::client_config(ca, identity)?;
+  let stream = TcpStream::connect(("127.0.0.1", port))?;
+  let mut stream = tls::connect(stream, Some((&config, "127.0.0.1")))?;
+
+  let request = json!({
+    "kind": "STREAM",
+    "addr": "127.0.0.1:8002",
+    "tickers": ["AAPL"],
+  });
+  stream.write_all(request.to_string().as_bytes())?;
+  stream.flush()?;
+
+  read_json::<StockResponse>(BufReader::new(&mut stream))
+}
+
+#[test]
+fn request_is_exchanged_over_tls() {
+  let pki = TestPki::generate("plain");
+  let (port, server) = spawn_server(&pki, None);
+
+  let response = send_request(port, &pki.path("ca.pem"), None).unwrap();
+  let request = server.join().unwrap().unwrap();
+
+  assert!(matches!(response.status, StockResponseStatus::Ok));
+  assert_eq!(request.tickers, vec!["AAPL"]);
+}
+
+#[test]
+fn client_certificate_is_verified() {
+  let pki = TestPki::generate("mutual");
+  let (port, server) = spawn_server(&pki, Some(&pki.path("ca.pem")));
+
+  let identity = (pki.path("client.pem"), pki.path("client.key"));
+  let response =
+    send_request(port, &pki.path("ca.pem"), Some((&identity.0, &identity.1)))
+      .unwrap();
+
+  assert!(matches!(response.status, StockResponseStatus::Ok));
+  assert!(server.join().unwrap().is_ok());
+}
+
+#[test]
+fn client_without_certificate_is_rejected() {
+  let pki = TestPki::generate("anonymous");
+  let (port, server) = spawn_server(&pki, Some(&pki.path("ca.pem")));
+
+  assert!(send_request(port, &pki.path("ca.pem"), None).is_err());
+  assert!(server.join().unwrap().is_err());
+}
+
+#[test]
+fn untrusted_server_is_rejected() {
+  let pki = TestPki::generate("server");
+  let other_pki = TestPki::generate("other");
+  let (port, server) = spawn_server(&pki, None);
+
+  assert!(send_request(port, &other_pki.path("ca.pem"), None).is_err());
+  assert!(server.join().unwrap().is_err());
+}
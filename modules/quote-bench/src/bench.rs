@@ -0,0 +1,197 @@
+use std::{
+  net::{IpAddr, Ipv4Addr, SocketAddr},
+  sync::{
+    Arc,
+    atomic::{AtomicBool, Ordering},
+    mpsc::{self, Receiver},
+  },
+  thread::{self, JoinHandle},
+  time::{Duration, Instant},
+};
+
+use anyhow::anyhow;
+use rand::seq::IndexedRandom;
+use tracing::{info, warn};
+
+use common::{
+  error::AppError,
+  stock::{Credentials, SubscriptionOptions},
+};
+
+use crate::{
+  configs::consts,
+  stats::{BenchSummary, SubscriberStats},
+  subscriber::Subscriber,
+};
+
+/// Server addresses and load parameters of a bench run
+#[derive(Debug, Clone)]
+pub struct BenchConfig {
+  pub server_tcp_addr: SocketAddr,
+  /// UDP port of server, which shares IP with TCP address
+  pub server_udp_port: u16,
+  /// IP subscribers receive quotes at, each on an ephemeral port
+  pub client_ip: IpAddr,
+  pub subscribers: usize,
+  /// Tickers, patterns or group names subscribers pick from
+  pub tickers: Vec<String>,
+  /// Tickers each subscriber picks at random
+  pub tickers_per_subscriber: usize,
+  pub options: SubscriptionOptions,
+  pub auth: Option<Credentials>,
+  /// Handshakes per second
+  pub connect_rate: f64,
+  /// Measurement time after the last handshake
+  pub duration: Duration,
+  /// Threads polling subscriber sockets
+  pub workers: usize,
+}
+
+impl BenchConfig {
+  /// Loopback subscribers with default load parameters
+  pub fn new(
+    server_tcp_addr: SocketAddr,
+    server_udp_port: u16,
+    tickers: Vec<String>,
+  ) -> Self {
+    Self {
+      server_tcp_addr,
+      server_udp_port,
+      client_ip: IpAddr::V4(Ipv4Addr::LOCALHOST),
+      subscribers: consts::SUBSCRIBERS,
+      tickers,
+      tickers_per_subscriber: consts::TICKERS_PER_SUBSCRIBER,
+      options: SubscriptionOptions::default(),
+      auth: None,
+      connect_rate: consts::CONNECT_RATE,
+      duration: consts::DURATION,
+      workers: consts::WORKERS,
+    }
+  }
+}
+
+/// # Load generation
+///
+/// Subscribes simulated subscribers one by one at the connect rate, each with
+/// its own UDP socket, random ticker set and heartbeats, and measures them
+/// until `duration` passes after the last handshake. Subscriber sockets are
+/// polled by a few worker threads, so thousands of subscribers don't take
+/// thousands of threads. Setting `shutdown` ends the run early, subscribers
+/// measured so far are summarized.
+pub fn run(
+  config: BenchConfig,
+  shutdown: Arc<AtomicBool>,
+) -> Result<BenchSummary, AppError> {
+  info!(
+    subscribers = config.subscribers,
+    connect_rate = config.connect_rate,
+    workers = config.workers,
+    "Start load generation:"
+  );
+
+  let stop = Arc::new(AtomicBool::new(false));
+  let (senders, workers): (Vec<_>, Vec<_>) = (0..config.workers.max(1))
+    .map(|_| {
+      let (tx, rx) = mpsc::channel();
+      (tx, spawn_worker(rx, Arc::clone(&stop)))
+    })
+    .unzip();
+
+  let mut summary = BenchSummary {
+    requested: config.subscribers,
+    ..BenchSummary::default()
+  };
+  let mut rng = rand::rng();
+  let connect_interval = Duration::from_secs_f64(1.0 / config.connect_rate);
+  let started = Instant::now();
+
+  for index in 0..config.subscribers {
+    if !wait_until(started + connect_interval.mul_f64(index as f64), &shutdown)
+    {
+      break;
+    }
+
+    let tickers = config
+      .tickers
+      .choose_multiple(&mut rng, config.tickers_per_subscriber)
+      .cloned()
+      .collect();
+    match Subscriber::subscribe(&config, tickers) {
+      // Worker failure is reported on join
+      Ok(subscriber) => {
+        let _ = senders[index % senders.len()].send(subscriber);
+      }
+      Err(AppError::SubscriptionRejected { message }) => {
+        *summary.rejected.entry(message).or_default() += 1;
+      }
+      Err(e) => {
+        warn!(err = %e, "Failed subscribing:");
+        summary.failed += 1;
+      }
+    }
+  }
+
+  info!(
+    accepted = config.subscribers
+      - summary.failed
+      - summary.rejected.values().sum::<usize>(),
+    duration = ?config.duration,
+    "Measure subscribers:"
+  );
+  wait_until(Instant::now() + config.duration, &shutdown);
+
+  stop.store(true, Ordering::Release);
+  drop(senders);
+  for worker in workers {
+    let stats = worker.join().map_err(|_| {
+      AppError::OtherError(anyhow!("Failed waiting for worker thread"))
+    })??;
+    summary.subscribers.extend(stats);
+  }
+  info!("Stop load generation");
+
+  Ok(summary)
+}
+
+/// Poll subscribers handed over through `subscribers` until `stop` is set
+fn spawn_worker(
+  subscribers: Receiver<Subscriber>,
+  stop: Arc<AtomicBool>,
+) -> JoinHandle<Result<Vec<SubscriberStats>, AppError>> {
+  thread::spawn(move || {
+    let mut active = Vec::new();
+    let mut buf = vec![0u8; 64 * 1024];
+
+    while !stop.load(Ordering::Acquire) {
+      active.extend(subscribers.try_iter());
+
+      let now = Instant::now();
+      let mut received = false;
+      for subscriber in &mut active {
+        received |= subscriber.poll(&mut buf)?;
+        subscriber.heartbeat(now)?;
+      }
+
+      if !received {
+        thread::sleep(consts::POLL_INTERVAL);
+      }
+    }
+    active.extend(subscribers.try_iter());
+
+    Ok(active.into_iter().map(Subscriber::finish).collect())
+  })
+}
+
+/// Sleep until `deadline`, `false` when shutdown comes earlier
+fn wait_until(deadline: Instant, shutdown: &AtomicBool) -> bool {
+  while !shutdown.load(Ordering::Acquire) {
+    let remaining = deadline.saturating_duration_since(Instant::now());
+    if remaining.is_zero() {
+      return true;
+    }
+
+    thread::sleep(remaining.min(consts::SHUTDOWN_POLL_INTERVAL));
+  }
+
+  false
+}
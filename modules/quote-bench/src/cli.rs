@@ -0,0 +1,62 @@
+use clap::Parser;
+use common::utils::{
+  path_validation, port_validation, rate_validation, server_address_validation,
+};
+use quote_bench::consts;
+use std::{
+  net::{IpAddr, Ipv4Addr, SocketAddr},
+  path::PathBuf,
+};
+
+#[derive(Debug, Parser)]
+#[command(version, about, next_line_help = true)]
+pub(crate) struct CliArgs {
+  /// Tickers, patterns or group names subscribers pick from
+  #[arg(short = 'f', long, value_name = "Tickers file", value_parser = path_validation)]
+  pub tickers_file: PathBuf,
+  #[arg(short = 's',long, value_name = "Server TCP address", value_parser = server_address_validation)]
+  pub server_tcp_addr: SocketAddr,
+  #[arg(short = 'S',long, value_name = "Server UDP port", value_parser = port_validation)]
+  pub server_udp_port: u16,
+  /// IP subscribers receive quotes at, each on an ephemeral port
+  #[arg(short = 'c', long, value_name = "Client IP", default_value_t = IpAddr::V4(Ipv4Addr::LOCALHOST))]
+  pub client_ip: IpAddr,
+  /// Simulated subscribers
+  #[arg(short = 'n', long, value_name = "Subscribers", default_value_t = consts::SUBSCRIBERS)]
+  pub subscribers: usize,
+  /// Tickers each subscriber picks at random from the tickers file
+  #[arg(long, value_name = "Tickers", default_value_t = consts::TICKERS_PER_SUBSCRIBER)]
+  pub tickers_per_subscriber: usize,
+  /// Handshakes per second, server refuses handshakes above its rate
+  #[arg(long, value_name = "Handshakes per second", default_value_t = consts::CONNECT_RATE, value_parser = rate_validation)]
+  pub connect_rate: f64,
+  /// Milliseconds of measurement after the last handshake
+  #[arg(short = 'd', long, value_name = "Milliseconds", default_value_t = consts::DURATION.as_millis() as u64)]
+  pub duration: u64,
+  /// Threads polling subscriber sockets
+  #[arg(long, value_name = "Threads", default_value_t = consts::WORKERS)]
+  pub workers: usize,
+  /// Receive only quotes changed since the previous update
+  #[arg(long)]
+  pub delta: bool,
+  /// Maximum quote updates per second of each subscriber
+  #[arg(long, value_name = "Updates per second", value_parser = rate_validation)]
+  pub max_rate: Option<f64>,
+  /// Receive quote datagrams encrypted and authenticated with the session key
+  #[arg(long)]
+  pub encrypt: bool,
+  /// Proposed heartbeat interval in milliseconds, server applies its limits
+  #[arg(long, value_name = "Milliseconds")]
+  pub heartbeat_interval: Option<u64>,
+  /// Username of every subscriber for server authentication
+  #[arg(short = 'u', long, value_name = "Username", requires = "password")]
+  pub username: Option<String>,
+  /// Password for server authentication
+  #[arg(
+    long,
+    value_name = "Password",
+    env = "QUOTE_BENCH_PASSWORD",
+    hide_env_values = true
+  )]
+  pub password: Option<String>,
+}
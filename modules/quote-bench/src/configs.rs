@@ -0,0 +1,17 @@
+pub mod consts {
+  use std::time::Duration;
+
+  pub const SUBSCRIBERS: usize = 100;
+  pub const TICKERS_PER_SUBSCRIBER: usize = 3;
+  // Server default handshake rate, faster handshakes are refused
+  pub const CONNECT_RATE: f64 = 50.0;
+  pub const DURATION: Duration = Duration::from_secs(30);
+  pub const WORKERS: usize = 4;
+  pub const TCP_STREAM_READ_TIMEOUT: Duration = Duration::from_secs(2);
+  pub const TCP_STREAM_WRITE_TIMEOUT: Duration = Duration::from_secs(2);
+  // Heartbeat interval of servers which don't negotiate it
+  pub const HEARTBEAT_INTERVAL: Duration = Duration::from_secs(1);
+  // Workers sleep when a pass over their sockets receives nothing
+  pub const POLL_INTERVAL: Duration = Duration::from_millis(1);
+  pub const SHUTDOWN_POLL_INTERVAL: Duration = Duration::from_millis(100);
+}
@@ -0,0 +1,14 @@
+//! This is the quote server load generation crate, which simulates many
+//! subscribers and measures how one server keeps up with them.
+//!
+//! [`run`] subscribes simulated subscribers and returns a [`BenchSummary`] of
+//! handshake latency, delivery rate, loss and quote latency.
+
+mod bench;
+pub mod configs;
+mod stats;
+mod subscriber;
+
+pub use bench::{BenchConfig, run};
+pub use configs::consts;
+pub use stats::{BenchSummary, SubscriberStats};
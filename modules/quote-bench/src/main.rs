@@ -0,0 +1,82 @@
+use std::{
+  io,
+  sync::{Arc, atomic::AtomicBool},
+  time::Duration,
+};
+
+use clap::Parser;
+use tracing::info;
+
+use common::{
+  error::AppError,
+  stock::{Credentials, SubscriptionOptions},
+  utils::{read_tickers, register_signal_hooks},
+};
+use quote_bench::BenchConfig;
+
+mod cli;
+
+use cli::CliArgs;
+
+fn main() -> Result<(), AppError> {
+  // Logs are kept apart from the summary written to stdout
+  tracing_subscriber::fmt()
+    .with_writer(io::stderr)
+    .with_line_number(true)
+    .with_thread_ids(true)
+    .init();
+
+  info!("Start bench");
+
+  let cli = CliArgs::parse();
+  let CliArgs {
+    tickers_file,
+    server_tcp_addr,
+    server_udp_port,
+    client_ip,
+    subscribers,
+    tickers_per_subscriber,
+    connect_rate,
+    duration,
+    workers,
+    delta,
+    max_rate,
+    encrypt,
+    heartbeat_interval,
+    username,
+    password,
+  } = cli;
+
+  let tickers = read_tickers(tickers_file)?;
+  let auth = username.map(|username| Credentials {
+    username,
+    password: password.unwrap_or_default(),
+  });
+
+  let shutdown = Arc::new(AtomicBool::new(false));
+  register_signal_hooks(&shutdown)?;
+
+  let summary = quote_bench::run(
+    BenchConfig {
+      client_ip,
+      subscribers,
+      tickers_per_subscriber,
+      options: SubscriptionOptions {
+        delta,
+        max_rate,
+        encrypt,
+        heartbeat_interval_ms: heartbeat_interval,
+        ..SubscriptionOptions::default()
+      },
+      auth,
+      connect_rate,
+      duration: Duration::from_millis(duration),
+      workers,
+      ..BenchConfig::new(server_tcp_addr, server_udp_port, tickers)
+    },
+    shutdown,
+  )?;
+  print!("{summary}");
+
+  Ok(())
+}
@@ -0,0 +1,250 @@
+use std::{collections::BTreeMap, fmt, time::Duration};
+
+use quote_client::latency::Histogram;
+
+/// Measurements of a simulated subscriber
+#[derive(Debug, Clone)]
+pub struct SubscriberStats {
+  /// Time from TCP connect to server response
+  pub handshake: Duration,
+  /// Tickers accepted by server
+  pub tickers: usize,
+  /// Server challenge was received and echoed
+  pub verified: bool,
+  /// Snapshot and delta datagrams received
+  pub datagrams: u64,
+  pub quotes: u64,
+  /// Datagrams missed in sequence gaps
+  pub lost: u64,
+  /// Server heartbeats received on idle subscription
+  pub heartbeats: u64,
+  /// Datagrams which failed to open or decode
+  pub rejected: u64,
+  /// Heartbeats and challenge echoes failed to be sent
+  pub failed_sends: u64,
+  /// Server sent `going_away` datagram
+  pub going_away: bool,
+  /// Receive time minus quote timestamp in microseconds
+  pub latency: Histogram,
+  /// Time since the subscription was accepted until the bench ended
+  pub subscribed_for: Duration,
+}
+
+impl SubscriberStats {
+  pub fn new(handshake: Duration, tickers: usize) -> Self {
+    Self {
+      handshake,
+      tickers,
+      verified: false,
+      datagrams: 0,
+      quotes: 0,
+      lost: 0,
+      heartbeats: 0,
+      rejected: 0,
+      failed_sends: 0,
+      going_away: false,
+      latency: Histogram::default(),
+      subscribed_for: Duration::ZERO,
+    }
+  }
+  /// Quotes received per second of subscription
+  pub fn delivery_rate(&self) -> f64 {
+    per_second(self.quotes, self.subscribed_for)
+  }
+}
+
+/// # Load generation summary
+///
+/// Handshakes of all requested subscribers, and delivery, loss and latency of
+/// accepted ones. Rates are per second of each subscription, so subscribers
+/// accepted late don't lower them. Latencies are receive time minus quote
+/// timestamp, which are comparable on a single host or synced clocks only.
+#[derive(Debug, Default)]
+pub struct BenchSummary {
+  pub requested: usize,
+  /// Subscribers accepted by server
+  pub subscribers: Vec<SubscriberStats>,
+  /// Server rejection messages with the number of subscribers they rejected
+  pub rejected: BTreeMap<String, usize>,
+  /// Handshakes failed without server response
+  pub failed: usize,
+}
+
+impl BenchSummary {
+  /// Handshake times of accepted subscribers in microseconds
+  pub fn handshake(&self) -> Histogram {
+    let mut histogram = Histogram::default();
+    for subscriber in &self.subscribers {
+      histogram.record(subscriber.handshake.as_micros() as u64);
+    }
+
+    histogram
+  }
+  /// Quote latencies of all subscribers in microseconds
+  pub fn latency(&self) -> Histogram {
+    let mut histogram = Histogram::default();
+    for subscriber in &self.subscribers {
+      histogram.merge(&subscriber.latency);
+    }
+
+    histogram
+  }
+  pub fn datagrams(&self) -> u64 {
+    self
+      .subscribers
+      .iter()
+      .map(|subscriber| subscriber.datagrams)
+      .sum()
+  }
+  pub fn lost(&self) -> u64 {
+    self
+      .subscribers
+      .iter()
+      .map(|subscriber| subscriber.lost)
+      .sum()
+  }
+  /// Fraction of datagrams missed in sequence gaps
+  pub fn loss(&self) -> f64 {
+    let sent = self.datagrams() + self.lost();
+    if sent == 0 {
+      return 0.0;
+    }
+
+    self.lost() as f64 / sent as f64
+  }
+  /// Quotes delivered per second to all subscribers
+  pub fn delivery_rate(&self) -> f64 {
+    self
+      .subscribers
+      .iter()
+      .map(SubscriberStats::delivery_rate)
+      .sum()
+  }
+}
+
+impl fmt::Display for BenchSummary {
+  fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+    let accepted = self.subscribers.len();
+    let count = |predicate: fn(&SubscriberStats) -> bool| {
+      self
+        .subscribers
+        .iter()
+        .filter(|subscriber| predicate(subscriber))
+        .count()
+    };
+    let rejected: usize = self.rejected.values().sum();
+
+    writeln!(
+      f,
+      "Subscribers:    {} requested, {accepted} accepted, {rejected} rejected, {} failed",
+      self.requested, self.failed
+    )?;
+    for (message, count) in &self.rejected {
+      writeln!(f, "Rejected:       {count} `{message}`")?;
+    }
+    if accepted == 0 {
+      return Ok(());
+    }
+
+    writeln!(f, "Handshake:      {}", percentiles(&self.handshake()))?;
+    writeln!(
+      f,
+      "Verified:       {} subscribers, {} received quotes, {} told going away",
+      count(|subscriber| subscriber.verified),
+      count(|subscriber| subscriber.quotes > 0),
+      count(|subscriber| subscriber.going_away),
+    )?;
+
+    let datagram_rate: f64 = self
+      .subscribers
+      .iter()
+      .map(|subscriber| {
+        per_second(subscriber.datagrams, subscriber.subscribed_for)
+      })
+      .sum();
+    writeln!(
+      f,
+      "Delivery:       {datagram_rate:.1} datagrams/s, {:.1} quotes/s, {:.2} quotes/s per subscriber",
+      self.delivery_rate(),
+      self.delivery_rate() / accepted as f64,
+    )?;
+    writeln!(
+      f,
+      "Loss:           {} of {} datagrams ({:.3}%), {} subscribers lost datagrams",
+      self.lost(),
+      self.datagrams() + self.lost(),
+      self.loss() * 100.0,
+      count(|subscriber| subscriber.lost > 0),
+    )?;
+
+    let rejected: u64 = self
+      .subscribers
+      .iter()
+      .map(|subscriber| subscriber.rejected)
+      .sum();
+    let failed_sends: u64 = self
+      .subscribers
+      .iter()
+      .map(|subscriber| subscriber.failed_sends)
+      .sum();
+    if rejected > 0 || failed_sends > 0 {
+      writeln!(
+        f,
+        "Errors:         {rejected} datagrams rejected, {failed_sends} sends failed"
+      )?;
+    }
+
+    let latency = self.latency();
+    if latency.count() == 0 {
+      return Ok(());
+    }
+    writeln!(f, "Latency:        {}", percentiles(&latency))?;
+
+    let measured: Vec<&Histogram> = self
+      .subscribers
+      .iter()
+      .map(|subscriber| &subscriber.latency)
+      .filter(|latency| latency.count() > 0)
+      .collect();
+    let p50 = spread(measured.iter().map(|latency| latency.percentile(0.5)));
+    let p99 = spread(measured.iter().map(|latency| latency.percentile(0.99)));
+    writeln!(f, "Per subscriber: p50 {p50}, p99 {p99}")
+  }
+}
+
+fn per_second(amount: u64, elapsed: Duration) -> f64 {
+  if elapsed.is_zero() {
+    return 0.0;
+  }
+
+  amount as f64 / elapsed.as_secs_f64()
+}
+
+fn micros(value: u64) -> Duration {
+  Duration::from_micros(value)
+}
+
+fn percentiles(histogram: &Histogram) -> String {
+  format!(
+    "p50 {:?}, p99 {:?}, max {:?}",
+    micros(histogram.percentile(0.5)),
+    micros(histogram.percentile(0.99)),
+    micros(histogram.max()),
+  )
+}
+
+/// Lowest, median and highest of per-subscriber values
+fn spread(values: impl Iterator<Item = u64>) -> String {
+  let mut values: Vec<u64> = values.collect();
+  values.sort_unstable();
+
+  match (values.first(), values.last()) {
+    (Some(min), Some(max)) => format!(
+      "{:?}..{:?} (median {:?})",
+      micros(*min),
+      micros(*max),
+      micros(values[values.len() / 2]),
+    ),
+    _ => "-".to_string(),
+  }
+}
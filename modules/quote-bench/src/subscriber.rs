@@ -0,0 +1,236 @@
+use std::{
+  io::{self, BufReader, Write},
+  net::{SocketAddr, TcpStream, UdpSocket},
+  time::{Duration, Instant, SystemTime, UNIX_EPOCH},
+};
+
+use anyhow::Context;
+use serde_json::json;
+
+use common::{
+  error::AppError,
+  seal::{DatagramCipher, ReplayWindow, SEALED_DATAGRAM_TAG},
+  session::{ClientDatagram, Heartbeat, SessionCredentials},
+  stock::{StockDatagram, StockRequest, StockResponse, StockResponseStatus},
+  utils::read_json,
+};
+
+use crate::{bench::BenchConfig, configs::consts, stats::SubscriberStats};
+
+/// Simulated subscriber speaking the wire protocol on its own UDP socket
+///
+/// Socket doesn't block, so a worker thread polls many subscribers in turn.
+#[derive(Debug)]
+pub(crate) struct Subscriber {
+  udp: UdpSocket,
+  server_udp_addr: SocketAddr,
+  session: SessionCredentials,
+  cipher: Option<DatagramCipher>,
+  window: ReplayWindow,
+  heartbeat_interval: Duration,
+  last_heartbeat: Option<Instant>,
+  counter: u64,
+  last_seq: u64,
+  subscribed_at: Instant,
+  stats: SubscriberStats,
+}
+
+impl Subscriber {
+  /// Bind an ephemeral UDP port and subscribe it to `tickers`, handshake time
+  /// spans TCP connect to server response
+  pub fn subscribe(
+    config: &BenchConfig,
+    tickers: Vec<String>,
+  ) -> Result<Self, AppError> {
+    let udp_addr = SocketAddr::new(config.client_ip, 0);
+    let udp =
+      UdpSocket::bind(udp_addr).map_err(|err| AppError::AddressBindError {
+        addr: udp_addr,
+        err,
+      })?;
+    udp
+      .set_nonblocking(true)
+      .map_err(|err| AppError::UdpSocketError { err })?;
+    let addr = udp
+      .local_addr()
+      .map_err(|err| AppError::UdpSocketError { err })?;
+
+    let started = Instant::now();
+    let mut stream = TcpStream::connect(config.server_tcp_addr).context(
+      format!("Failed connecting to server {}", config.server_tcp_addr),
+    )?;
+    stream
+      .set_nodelay(true)
+      .map_err(|err| AppError::TcpStreamError { err })?;
+    stream
+      .set_read_timeout(Some(consts::TCP_STREAM_READ_TIMEOUT))
+      .map_err(|err| AppError::TcpStreamError { err })?;
+    stream
+      .set_write_timeout(Some(consts::TCP_STREAM_WRITE_TIMEOUT))
+      .map_err(|err| AppError::TcpStreamError { err })?;
+
+    let stock_request = StockRequest {
+      kind: "STREAM".to_string(),
+      addr,
+      tickers,
+      options: config.options.clone(),
+      auth: config.auth.clone(),
+    };
+    stream
+      .write_all(json!(stock_request).to_string().as_bytes())
+      .context("Failed writing to TCP stream")?;
+    stream.flush()?;
+
+    let StockResponse {
+      status,
+      message,
+      report,
+      session,
+      heartbeat,
+    } = read_json::<StockResponse>(BufReader::new(&mut stream))?;
+    let handshake = started.elapsed();
+
+    // Bench doesn't retry, refusals by server limits are counted as rejections
+    if let StockResponseStatus::Error | StockResponseStatus::Retry = status {
+      return Err(AppError::SubscriptionRejected { message });
+    }
+
+    let session = session.context("Response without session credentials")?;
+    let cipher = config
+      .options
+      .encrypt
+      .then(|| DatagramCipher::new(&session))
+      .transpose()?;
+    let mut server_udp_addr = config.server_tcp_addr;
+    server_udp_addr.set_port(config.server_udp_port);
+
+    Ok(Self {
+      udp,
+      server_udp_addr,
+      session,
+      cipher,
+      window: ReplayWindow::default(),
+      heartbeat_interval: heartbeat
+        .map(|heartbeat| heartbeat.interval())
+        .unwrap_or(consts::HEARTBEAT_INTERVAL),
+      last_heartbeat: None,
+      counter: 0,
+      last_seq: 0,
+      subscribed_at: Instant::now(),
+      stats: SubscriberStats::new(
+        handshake,
+        report.map_or(0, |report| report.accepted.len()),
+      ),
+    })
+  }
+  /// Receive datagrams until none are left, `true` when any arrived
+  pub fn poll(&mut self, buf: &mut [u8]) -> Result<bool, AppError> {
+    let mut received = false;
+
+    loop {
+      let n = match self.udp.recv(buf) {
+        Ok(n) => n,
+        Err(e) if e.kind() == io::ErrorKind::WouldBlock => {
+          return Ok(received);
+        }
+        Err(e) => return Err(e).context("udp_socket.recv failed")?,
+      };
+      let received_at = SystemTime::now()
+        .duration_since(UNIX_EPOCH)
+        .unwrap_or_default()
+        .as_micros() as u64;
+
+      received = true;
+      self.receive(&buf[..n], received_at);
+    }
+  }
+  /// Send a signed heartbeat once the negotiated interval has passed
+  pub fn heartbeat(&mut self, now: Instant) -> Result<(), AppError> {
+    if self
+      .last_heartbeat
+      .is_some_and(|last_sent| now < last_sent + self.heartbeat_interval)
+    {
+      return Ok(());
+    }
+
+    self.last_heartbeat = Some(now);
+    self.counter += 1;
+    let heartbeat = Heartbeat::sign(&self.session, self.counter)?;
+    self.send(&ClientDatagram::Heartbeat(heartbeat));
+
+    Ok(())
+  }
+  pub fn finish(self) -> SubscriberStats {
+    SubscriberStats {
+      subscribed_for: self.subscribed_at.elapsed(),
+      ..self.stats
+    }
+  }
+  fn receive(&mut self, message: &[u8], received_at_us: u64) {
+    let sealed = message.first() == Some(&SEALED_DATAGRAM_TAG);
+    let opened = match (&self.cipher, sealed) {
+      (Some(cipher), true) => match cipher.open(message) {
+        Ok((seq, payload)) if self.window.accept(seq) => Some(payload),
+        _ => {
+          self.stats.rejected += 1;
+          return;
+        }
+      },
+      _ => None,
+    };
+    let Ok(datagram) = serde_json::from_slice::<StockDatagram>(
+      opened.as_deref().unwrap_or(message),
+    ) else {
+      self.stats.rejected += 1;
+      return;
+    };
+
+    if let StockDatagram::Challenge { nonce } = datagram {
+      self.stats.verified = true;
+      self.send(&ClientDatagram::Verification { nonce });
+      return;
+    }
+    // Challenges precede the session key, other datagrams are sealed
+    if self.cipher.is_some() && !sealed {
+      self.stats.rejected += 1;
+      return;
+    }
+
+    if let Some(seq) = datagram.seq() {
+      if seq > self.last_seq + 1 {
+        self.stats.lost += seq - self.last_seq - 1;
+      }
+      self.last_seq = self.last_seq.max(seq);
+    }
+
+    match datagram {
+      StockDatagram::Snapshot { quotes, .. }
+      | StockDatagram::Delta { quotes, .. } => {
+        self.stats.datagrams += 1;
+        self.stats.quotes += quotes.len() as u64;
+
+        for timestamp in quotes.iter().filter_map(|quote| quote.timestamp) {
+          self
+            .stats
+            .latency
+            .record(received_at_us.saturating_sub(timestamp * 1000));
+        }
+      }
+      StockDatagram::Heartbeat { .. } => self.stats.heartbeats += 1,
+      StockDatagram::GoingAway { .. } => self.stats.going_away = true,
+      StockDatagram::Challenge { .. } => {}
+    }
+  }
+  /// Failed sends are counted, server evicts subscribers which keep failing
+  fn send(&mut self, datagram: &ClientDatagram) {
+    let message = json!(datagram).to_string();
+
+    if self
+      .udp
+      .send_to(message.as_bytes(), self.server_udp_addr)
+      .is_err()
+    {
+      self.stats.failed_sends += 1;
+    }
+  }
+}
@@ -0,0 +1,89 @@
+use std::{
+  sync::{Arc, atomic::AtomicBool},
+  time::Duration,
+};
+
+use quote_bench::BenchConfig;
+use quote_server::{AccessPolicy, Limits, LocalAddrs, ServerHandle};
+use test_support::{TICKERS, test_server};
+
+/// Test server with `limits` and short heartbeats
+fn bench_server(limits: Limits) -> ServerHandle {
+  test_server()
+    .policy(AccessPolicy {
+      limits: Limits {
+        min_heartbeat_interval: Duration::from_millis(10),
+        ..limits
+      },
+      ..AccessPolicy::default()
+    })
+    .spawn()
+    .unwrap()
+}
+
+fn bench_config(server: &ServerHandle, subscribers: usize) -> BenchConfig {
+  let LocalAddrs { tcp, udp } = server.local_addrs();
+
+  BenchConfig {
+    subscribers,
+    tickers_per_subscriber: 2,
+    connect_rate: 200.0,
+    duration: Duration::from_millis(500),
+    workers: 2,
+    ..BenchConfig::new(tcp, udp.port(), TICKERS.map(String::from).to_vec())
+  }
+}
+
+#[test]
+fn subscribers_are_measured() {
+  let server = bench_server(Limits::default());
+
+  let summary = quote_bench::run(
+    bench_config(&server, 10),
+    Arc::new(AtomicBool::new(false)),
+  )
+  .unwrap();
+
+  assert_eq!(summary.subscribers.len(), 10);
+  assert_eq!(summary.failed, 0);
+  for subscriber in &summary.subscribers {
+    assert_eq!(subscriber.tickers, 2);
+    assert!(subscriber.verified);
+    assert!(subscriber.quotes > 0);
+    assert!(subscriber.latency.count() > 0);
+  }
+  assert_eq!(summary.handshake().count(), 10);
+  assert!(summary.delivery_rate() > 0.0);
+  assert!(summary.to_string().contains("10 requested, 10 accepted"));
+}
+
+#[test]
+fn refused_subscribers_are_counted() {
+  let server = bench_server(Limits {
+    max_subscriptions_per_ip: 3,
+    ..Limits::default()
+  });
+
+  let summary = quote_bench::run(
+    bench_config(&server, 5),
+    Arc::new(AtomicBool::new(false)),
+  )
+  .unwrap();
+
+  assert_eq!(summary.subscribers.len(), 3);
+  assert_eq!(summary.rejected.values().sum::<usize>(), 2);
+}
+
+#[test]
+fn shutdown_ends_bench_early() {
+  let server = bench_server(Limits::default());
+
+  let summary = quote_bench::run(
+    bench_config(&server, 10),
+    Arc::new(AtomicBool::new(true)),
+  )
+  .unwrap();
+
+  assert_eq!(summary.requested, 10);
+  assert!(summary.subscribers.is_empty());
+}
@@ -0,0 +1,676 @@
+use std::{
+  collections::{HashMap, VecDeque},
+  fmt,
+  fs::{File, OpenOptions},
+  io::{self, BufRead, BufReader, Write},
+  path::{Path, PathBuf},
+  process::{Child, Command, Stdio},
+  str::FromStr,
+  thread,
+  time::{Duration, Instant},
+};
+
+use anyhow::Context;
+use tracing::warn;
+
+use common::{error::AppError, stock::StockQuote, utils::window_validation};
+
+use crate::configs::consts;
+
+/// Quote value a rule is checked against
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub enum Metric {
+  Price,
+  Volume,
+  /// Price change in percent since the earliest price within the window
+  PctChange(Duration),
+  /// Volume relative to the average volume of previous quotes within the
+  /// window
+  VolumeSpike(Duration),
+}
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum Comparison {
+  Above,
+  AtLeast,
+  Below,
+  AtMost,
+}
+
+impl Comparison {
+  fn holds(self, value: f64, threshold: f64) -> bool {
+    match self {
+      Self::Above => value > threshold,
+      Self::AtLeast => value >= threshold,
+      Self::Below => value < threshold,
+      Self::AtMost => value <= threshold,
+    }
+  }
+  /// Value moved back past the threshold by `margin`
+  fn clears(self, value: f64, threshold: f64, margin: f64) -> bool {
+    match self {
+      Self::Above | Self::AtLeast => value < threshold - margin,
+      Self::Below | Self::AtMost => value > threshold + margin,
+    }
+  }
+}
+
+impl FromStr for Comparison {
+  type Err = anyhow::Error;
+
+  fn from_str(str: &str) -> Result<Self, Self::Err> {
+    match str {
+      ">" => Ok(Self::Above),
+      ">=" => Ok(Self::AtLeast),
+      "<" => Ok(Self::Below),
+      "<=" => Ok(Self::AtMost),
+      _ => anyhow::bail!("Unknown comparison `{str}`"),
+    }
+  }
+}
+
+/// # Alert rule
+///
+/// One rule per line, as `[TICKER] CONDITION`, rules without ticker apply to
+/// every ticker:
+///
+/// - `AAPL > 200` Price above threshold, `price` may be written explicitly
+/// - `AAPL volume >= 10000` Volume compared to threshold
+/// - `TSLA pct_change_5m < -3` Price change in percent within the window,
+///   windows are written in `s`, `m` or `h`
+/// - `volume spike 3x avg` Volume at least 3 times the average volume of
+///   previous quotes within 5 minutes, or the window of `avg_1m`
+///
+/// Comparisons are `>`, `>=`, `<` and `<=`.
+#[derive(Debug, Clone, PartialEq)]
+pub struct AlertRule {
+  /// Ticker the rule applies to, every ticker when not set
+  pub ticker: Option<String>,
+  pub metric: Metric,
+  pub comparison: Comparison,
+  pub threshold: f64,
+  text: String,
+}
+
+impl AlertRule {
+  fn window(&self) -> Duration {
+    match self.metric {
+      Metric::PctChange(window) | Metric::VolumeSpike(window) => window,
+      Metric::Price | Metric::Volume => Duration::ZERO,
+    }
+  }
+  fn applies_to(&self, ticker: &str) -> bool {
+    self.ticker.as_deref().is_none_or(|rule| rule == ticker)
+  }
+}
+
+impl FromStr for AlertRule {
+  type Err = AppError;
+
+  fn from_str(str: &str) -> Result<Self, Self::Err> {
+    let invalid = |reason: String| AppError::InvalidAlertRule {
+      line: str.to_string(),
+      reason,
+    };
+    let mut tokens: Vec<&str> = str.split_whitespace().collect();
+
+    let ticker = match tokens.first() {
+      Some(token)
+        if !is_metric(token) && token.parse::<Comparison>().is_err() =>
+      {
+        Some(tokens.remove(0).to_uppercase())
+      }
+      _ => None,
+    };
+
+    let (metric, comparison, threshold) = match tokens.as_slice() {
+      ["volume", "spike", factor, average] => {
+        let factor = factor
+          .strip_suffix('x')
+          .and_then(|factor| factor.parse::<f64>().ok())
+          .filter(|factor| factor.is_finite() && *factor > 0.0)
+          .ok_or_else(|| invalid(format!("Invalid spike factor `{factor}`")))?;
+        let window = match *average {
+          "avg" => consts::ALERT_AVERAGE_WINDOW,
+          average => average
+            .strip_prefix("avg_")
+            .ok_or_else(|| anyhow::anyhow!("Unknown average `{average}`"))
+            .and_then(window_validation)
+            .map_err(|e| invalid(e.to_string()))?,
+        };
+
+        (Metric::VolumeSpike(window), Comparison::AtLeast, factor)
+      }
+      [metric, comparison, threshold] if is_metric(metric) => {
+        let metric = match *metric {
+          "price" => Metric::Price,
+          "volume" => Metric::Volume,
+          metric => metric
+            .strip_prefix("pct_change_")
+            .ok_or_else(|| anyhow::anyhow!("Unknown metric `{metric}`"))
+            .and_then(window_validation)
+            .map(Metric::PctChange)
+            .map_err(|e| invalid(e.to_string()))?,
+        };
+
+        let (comparison, threshold) = parse_condition(comparison, threshold)
+          .map_err(|e| invalid(e.to_string()))?;
+
+        (metric, comparison, threshold)
+      }
+      [comparison, threshold] => {
+        let (comparison, threshold) = parse_condition(comparison, threshold)
+          .map_err(|e| invalid(e.to_string()))?;
+
+        (Metric::Price, comparison, threshold)
+      }
+      _ => return Err(invalid("Expected `[TICKER] CONDITION`".to_string())),
+    };
+
+    Ok(Self {
+      ticker,
+      metric,
+      comparison,
+      threshold,
+      text: str.split_whitespace().collect::<Vec<_>>().join(" "),
+    })
+  }
+}
+
+impl fmt::Display for AlertRule {
+  fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+    f.write_str(&self.text)
+  }
+}
+
+/// Read alert rules from a file, empty lines and lines starting with `#` are
+/// skipped
+///
+/// # Example
+///
+/// ```
+/// use std::path::Path;
+///
+/// use quote_client::alerts::{Metric, read_alert_rules};
+///
+/// let rules = read_alert_rules(Path::new("../../mocks/client-alerts.txt"))
+///   .unwrap();
+///
+/// assert_eq!(rules[0].ticker.as_deref(), Some("AAPL"));
+/// assert_eq!(rules[0].metric, Metric::Price);
+/// assert_eq!(rules[2].ticker, None);
+/// assert_eq!(rules[2].to_string(), "volume spike 3x avg");
+/// ```
+pub fn read_alert_rules(path: &Path) -> Result<Vec<AlertRule>, AppError> {
+  let rules_file = File::open(path).context("Failed reading alert rules")?;
+  let mut rules = Vec::new();
+
+  for line in BufReader::new(rules_file).lines() {
+    let line = line?;
+    let line = line.trim();
+    if line.is_empty() || line.starts_with('#') {
+      continue;
+    }
+
+    rules.push(line.parse()?);
+  }
+
+  Ok(rules)
+}
+
+fn is_metric(token: &str) -> bool {
+  matches!(token, "price" | "volume") || token.starts_with("pct_change_")
+}
+
+/// Comparison and threshold as `> 200`
+fn parse_condition(
+  comparison: &str,
+  threshold: &str,
+) -> anyhow::Result<(Comparison, f64)> {
+  let threshold = threshold
+    .parse::<f64>()
+    .ok()
+    .filter(|threshold| threshold.is_finite())
+    .context(format!("Invalid threshold `{threshold}`"))?;
+
+  Ok((comparison.parse()?, threshold))
+}
+
+/// Debounce and hysteresis of alerts
+#[derive(Debug, Clone, Copy)]
+pub struct AlertOptions {
+  /// Minimum time between alerts of a rule for a ticker
+  pub cooldown: Duration,
+  /// Fraction of threshold the value has to move back past the threshold,
+  /// before the rule alerts again, thresholds below 1 count as 1
+  pub hysteresis: f64,
+}
+
+impl Default for AlertOptions {
+  fn default() -> Self {
+    Self {
+      cooldown: consts::ALERT_COOLDOWN,
+      hysteresis: consts::ALERT_HYSTERESIS,
+    }
+  }
+}
+
+/// Rule triggered by a quote
+#[derive(Debug, Clone, PartialEq, serde::Serialize)]
+pub struct Alert {
+  pub ticker: String,
+  pub rule: String,
+  /// Rule metric value of the quote
+  pub value: f64,
+  /// Quote timestamp, or receive time of quotes without timestamp
+  pub timestamp: u64,
+}
+
+impl fmt::Display for Alert {
+  fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+    write!(f, "{} `{}` at {:.4}", self.ticker, self.rule, self.value)
+  }
+}
+
+#[derive(Debug, Clone, Copy)]
+struct Sample {
+  timestamp: u64,
+  price: Option<f64>,
+  volume: Option<u32>,
+}
+
+#[derive(Debug, Clone, Copy)]
+struct RuleState {
+  /// Rule alerts once the condition holds
+  armed: bool,
+  last_alert: Option<u64>,
+}
+
+/// # Alert monitor
+///
+/// Evaluates rules on every quote. A rule alerts once when its condition
+/// starts to hold, and alerts again only after the value moves back past the
+/// threshold by the hysteresis margin and the cooldown since the previous
+/// alert has passed. Times are quote timestamps, so replayed feeds alert the
+/// same way.
+///
+/// ```
+/// use common::stock::StockQuote;
+/// use quote_client::alerts::{AlertMonitor, AlertOptions};
+///
+/// let rules = vec!["AAPL > 200".parse().unwrap()];
+/// let mut monitor = AlertMonitor::new(rules, AlertOptions::default());
+/// let mut alerts = 0;
+///
+/// // Price oscillates around the threshold within the hysteresis margin
+/// let prices = [199.0, 201.0, 199.5, 201.0, 200.5];
+/// for (timestamp, price) in (0..).zip(prices) {
+///   let quote = StockQuote {
+///     ticker: "AAPL".to_string(),
+///     price: Some(price),
+///     volume: None,
+///     timestamp: Some(timestamp),
+///   };
+///   alerts += monitor.evaluate(&quote, 0).len();
+/// }
+///
+/// assert_eq!(alerts, 1);
+/// ```
+#[derive(Debug)]
+pub struct AlertMonitor {
+  rules: Vec<AlertRule>,
+  options: AlertOptions,
+  /// History of quotes within the longest rule window per ticker
+  history: HashMap<String, VecDeque<Sample>>,
+  retention: u64,
+  states: HashMap<(usize, String), RuleState>,
+}
+
+impl AlertMonitor {
+  pub fn new(rules: Vec<AlertRule>, options: AlertOptions) -> Self {
+    let retention = rules
+      .iter()
+      .map(|rule| rule.window().as_millis() as u64)
+      .max()
+      .unwrap_or_default();
+
+    Self {
+      rules,
+      options,
+      history: HashMap::new(),
+      retention,
+      states: HashMap::new(),
+    }
+  }
+  /// Alerts of rules triggered by `quote`, `received_at` milliseconds since
+  /// Unix epoch stand for missing quote timestamp
+  pub fn evaluate(
+    &mut self,
+    quote: &StockQuote,
+    received_at: u64,
+  ) -> Vec<Alert> {
+    let timestamp = quote.timestamp.unwrap_or(received_at);
+    let history = self.history.entry(quote.ticker.clone()).or_default();
+    let mut alerts = Vec::new();
+
+    for (index, rule) in self.rules.iter().enumerate() {
+      if !rule.applies_to(&quote.ticker) {
+        continue;
+      }
+      let Some(value) = metric_value(rule.metric, quote, timestamp, history)
+      else {
+        continue;
+      };
+
+      let state =
+        self
+          .states
+          .entry((index, quote.ticker.clone()))
+          .or_insert(RuleState {
+            armed: true,
+            last_alert: None,
+          });
+      let cooled_down = state.last_alert.is_none_or(|last_alert| {
+        timestamp.saturating_sub(last_alert)
+          >= self.options.cooldown.as_millis() as u64
+      });
+
+      if rule.comparison.holds(value, rule.threshold) {
+        if state.armed && cooled_down {
+          state.armed = false;
+          state.last_alert = Some(timestamp);
+          alerts.push(Alert {
+            ticker: quote.ticker.clone(),
+            rule: rule.to_string(),
+            value,
+            timestamp,
+          });
+        }
+      } else if rule.comparison.clears(
+        value,
+        rule.threshold,
+        // Zero and small thresholds would re-arm on any move back
+        rule.threshold.abs().max(1.0) * self.options.hysteresis,
+      ) {
+        state.armed = true;
+      }
+    }
+
+    history.push_back(Sample {
+      timestamp,
+      price: quote.price,
+      volume: quote.volume,
+    });
+    while history.front().is_some_and(|sample| {
+      sample.timestamp < timestamp.saturating_sub(self.retention)
+    }) {
+      history.pop_front();
+    }
+
+    alerts
+  }
+}
+
+/// Rule metric of `quote`, `None` when quote or history lack the data
+fn metric_value(
+  metric: Metric,
+  quote: &StockQuote,
+  timestamp: u64,
+  history: &VecDeque<Sample>,
+) -> Option<f64> {
+  let within = |window: Duration| {
+    let since = timestamp.saturating_sub(window.as_millis() as u64);
+    history
+      .iter()
+      .filter(move |sample| sample.timestamp >= since)
+  };
+
+  match metric {
+    Metric::Price => quote.price,
+    Metric::Volume => quote.volume.map(f64::from),
+    Metric::PctChange(window) => {
+      let price = quote.price?;
+      let reference = within(window)
+        .find_map(|sample| sample.price)
+        .filter(|reference| *reference != 0.0)?;
+
+      Some((price - reference) / reference * 100.0)
+    }
+    Metric::VolumeSpike(window) => {
+      let volume = f64::from(quote.volume?);
+      let (count, total) = within(window)
+        .filter_map(|sample| sample.volume)
+        .fold((0u32, 0f64), |(count, total), volume| {
+          (count + 1, total + f64::from(volume))
+        });
+      let average = total / f64::from(count);
+
+      (count > 0 && average > 0.0).then(|| volume / average)
+    }
+  }
+}
+
+/// Destination of alerts: `-` for stdout, `exec:COMMAND` hook or a file
+#[derive(Debug, Clone, Default, PartialEq, Eq)]
+pub enum AlertTarget {
+  #[default]
+  Stdout,
+  File(PathBuf),
+  /// Shell command run per alert with `ALERT_*` environment variables
+  Exec(String),
+}
+
+impl FromStr for AlertTarget {
+  type Err = anyhow::Error;
+
+  fn from_str(str: &str) -> Result<Self, Self::Err> {
+    match str.trim() {
+      "" => anyhow::bail!("Alerts output should not be empty"),
+      "-" => Ok(Self::Stdout),
+      target => match target.strip_prefix("exec:").map(str::trim) {
+        Some("") => anyhow::bail!("Alerts hook command should not be empty"),
+        Some(command) => Ok(Self::Exec(command.to_string())),
+        None => Ok(Self::File(PathBuf::from(target))),
+      },
+    }
+  }
+}
+
+impl fmt::Display for AlertTarget {
+  fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+    match self {
+      Self::Stdout => f.write_str("-"),
+      Self::File(path) => write!(f, "{}", path.display()),
+      Self::Exec(command) => write!(f, "exec:{command}"),
+    }
+  }
+}
+
+/// Writes alerts as `JSON` lines, or runs the hook per alert
+///
+/// Hooks run concurrently up to a limit, failed hooks are logged and don't
+/// stop the feed. Running hooks are waited for when the writer is dropped.
+pub struct AlertWriter {
+  target: AlertTarget,
+  writer: Option<Box<dyn Write + Send>>,
+  /// Running hooks, reaped on following alerts
+  hooks: Vec<Child>,
+}
+
+impl AlertWriter {
+  /// Writer to stdout, appending to a file or running the hook
+  pub fn open(target: AlertTarget) -> Result<Self, AppError> {
+    let writer: Option<Box<dyn Write + Send>> = match &target {
+      AlertTarget::Stdout => Some(Box::new(io::stdout())),
+      AlertTarget::File(path) => Some(Box::new(
+        OpenOptions::new()
+          .append(true)
+          .create(true)
+          .open(path)
+          .context(format!("Failed opening alerts file {path:?}"))?,
+      )),
+      AlertTarget::Exec(_) => None,
+    };
+
+    Ok(Self {
+      target,
+      writer,
+      hooks: Vec::new(),
+    })
+  }
+  pub fn write(&mut self, alert: &Alert) -> Result<(), AppError> {
+    if let Some(writer) = &mut self.writer {
+      let mut line =
+        serde_json::to_vec(alert).context("Failed serializing alert")?;
+      line.push(b'\n');
+      writer.write_all(&line)?;
+      writer.flush()?;
+
+      return Ok(());
+    }
+
+    self.reap_hooks();
+
+    if let AlertTarget::Exec(command) = &self.target {
+      if self.hooks.len() >= consts::ALERT_MAX_HOOKS {
+        warn!(alert = %alert, running = self.hooks.len(), "Alert hook skipped, too many running:");
+        return Ok(());
+      }
+
+      let hook = Command::new("sh")
+        .arg("-c")
+        .arg(command)
+        .env("ALERT_TICKER", &alert.ticker)
+        .env("ALERT_RULE", &alert.rule)
+        .env("ALERT_VALUE", alert.value.to_string())
+        .env("ALERT_TIMESTAMP", alert.timestamp.to_string())
+        .stdin(Stdio::null())
+        .spawn();
+
+      match hook {
+        Ok(hook) => self.hooks.push(hook),
+        Err(e) => {
+          warn!(err = %e, command = %command, "Failed running alert hook:")
+        }
+      }
+    }
+
+    Ok(())
+  }
+  /// Forget finished hooks, failures are logged
+  fn reap_hooks(&mut self) {
+    self.hooks.retain_mut(|hook| match hook.try_wait() {
+      Ok(Some(status)) => {
+        if !status.success() {
+          warn!(status = %status, "Alert hook failed:");
+        }
+        false
+      }
+      Ok(None) => true,
+      Err(e) => {
+        warn!(err = %e, "Failed waiting for alert hook:");
+        false
+      }
+    });
+  }
+}
+
+impl Drop for AlertWriter {
+  /// Wait for running hooks, hooks still running after the timeout are killed
+  fn drop(&mut self) {
+    let deadline = Instant::now() + consts::ALERT_HOOK_EXIT_TIMEOUT;
+
+    self.reap_hooks();
+    while !self.hooks.is_empty() && Instant::now() < deadline {
+      thread::sleep(consts::SHUTDOWN_POLL_INTERVAL);
+      self.reap_hooks();
+    }
+
+    for hook in &mut self.hooks {
+      warn!(pid = hook.id(), "Alert hook killed on exit:");
+      let _ = hook.kill();
+      let _ = hook.wait();
+    }
+  }
+}
+
+#[cfg(test)]
+mod tests {
+  use super::*;
+
+  fn quote(timestamp: u64, price: f64, volume: u32) -> StockQuote {
+    StockQuote {
+      ticker: "AAPL".to_string(),
+      price: Some(price),
+      volume: Some(volume),
+      timestamp: Some(timestamp),
+    }
+  }
+
+  fn alert() -> Alert {
+    Alert {
+      ticker: "AAPL".to_string(),
+      rule: "AAPL > 200".to_string(),
+      value: 201.0,
+      timestamp: 0,
+    }
+  }
+
+  #[test]
+  fn rules_rearm_past_hysteresis_margin() {
+    let rules = vec!["AAPL > 200".parse().unwrap()];
+    let mut monitor = AlertMonitor::new(
+      rules,
+      AlertOptions {
+        cooldown: Duration::ZERO,
+        ..AlertOptions::default()
+      },
+    );
+
+    // 199 is within the margin, 197 is past it
+    let prices = [201.0, 199.0, 201.0, 197.0, 201.0];
+    let alerts: Vec<usize> = (0..)
+      .zip(prices)
+      .map(|(timestamp, price)| {
+        monitor.evaluate(&quote(timestamp, price, 100), 0).len()
+      })
+      .collect();
+
+    assert_eq!(alerts, [1, 0, 0, 0, 1]);
+  }
+
+  #[test]
+  fn zero_threshold_keeps_hysteresis_margin() {
+    let rules = vec!["AAPL pct_change_1m > 0".parse().unwrap()];
+    let mut monitor = AlertMonitor::new(
+      rules,
+      AlertOptions {
+        cooldown: Duration::ZERO,
+        ..AlertOptions::default()
+      },
+    );
+
+    // Change oscillates around zero within the margin
+    let prices = [100.0, 100.1, 99.995, 100.1];
+    let alerts: usize = (0..)
+      .zip(prices)
+      .map(|(timestamp, price)| {
+        monitor.evaluate(&quote(timestamp, price, 100), 0).len()
+      })
+      .sum();
+
+    assert_eq!(alerts, 1);
+  }
+
+  #[test]
+  fn failed_hooks_dont_fail_writes() {
+    let mut writer =
+      AlertWriter::open(AlertTarget::Exec("exit 1".to_string())).unwrap();
+
+    for _ in 0..consts::ALERT_MAX_HOOKS + 1 {
+      writer.write(&alert()).unwrap();
+    }
+
+    assert!(writer.hooks.len() <= consts::ALERT_MAX_HOOKS);
+  }
+}
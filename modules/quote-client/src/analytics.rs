@@ -0,0 +1,341 @@
+use std::{
+  collections::{BTreeMap, VecDeque},
+  io::Write,
+  time::Duration,
+};
+
+use anyhow::Context;
+
+use common::{error::AppError, stock::StockQuote};
+
+use crate::output::{
+  OutputFormat, OutputTarget, csv_field, open_target, optional,
+};
+
+const CSV_HEADER: &str =
+  "ticker,window_ms,ticks,vwap,sma,ema,min,max,volatility,timestamp";
+
+/// Rolling statistics of a ticker within a window
+#[derive(Debug, Clone, PartialEq, serde::Serialize)]
+pub struct WindowStats {
+  pub ticker: String,
+  pub window_ms: u64,
+  /// Quotes received within the window
+  pub ticks: usize,
+  /// Volume weighted average price
+  #[serde(skip_serializing_if = "Option::is_none")]
+  pub vwap: Option<f64>,
+  /// Simple moving average of prices
+  #[serde(skip_serializing_if = "Option::is_none")]
+  pub sma: Option<f64>,
+  /// Exponential moving average of prices, with the window as time constant
+  #[serde(skip_serializing_if = "Option::is_none")]
+  pub ema: Option<f64>,
+  #[serde(skip_serializing_if = "Option::is_none")]
+  pub min: Option<f64>,
+  #[serde(skip_serializing_if = "Option::is_none")]
+  pub max: Option<f64>,
+  /// Realized volatility, square root of summed squared log returns in
+  /// percent
+  #[serde(skip_serializing_if = "Option::is_none")]
+  pub volatility: Option<f64>,
+  /// Time the statistics are computed at
+  pub timestamp: u64,
+}
+
+#[derive(Debug, Clone, Copy)]
+struct Sample {
+  timestamp: u64,
+  price: Option<f64>,
+  volume: Option<u32>,
+}
+
+#[derive(Debug, Default)]
+struct TickerSeries {
+  samples: VecDeque<Sample>,
+  /// Moving average and its update time per window
+  emas: Vec<Option<(f64, u64)>>,
+}
+
+/// # Rolling statistics
+///
+/// Keeps quotes of each ticker within the longest window and computes VWAP,
+/// SMA, EMA, min and max price, realized volatility and tick count per
+/// window. Times are quote timestamps, so replayed feeds produce the same
+/// statistics.
+///
+/// ```
+/// use std::time::Duration;
+///
+/// use common::stock::StockQuote;
+/// use quote_client::analytics::RollingStats;
+///
+/// let mut stats = RollingStats::new(vec![Duration::from_secs(60)]);
+/// for (timestamp, price, volume) in [(0, 10.0, 100), (1000, 20.0, 300)] {
+///   let quote = StockQuote {
+///     ticker: "AAPL".to_string(),
+///     price: Some(price),
+///     volume: Some(volume),
+///     timestamp: Some(timestamp),
+///   };
+///   stats.record(&quote, 0);
+/// }
+///
+/// let aapl = &stats.stats()[0];
+/// assert_eq!(aapl.ticks, 2);
+/// assert_eq!(aapl.vwap, Some(17.5));
+/// assert_eq!(aapl.sma, Some(15.0));
+/// assert_eq!((aapl.min, aapl.max), (Some(10.0), Some(20.0)));
+/// ```
+#[derive(Debug)]
+pub struct RollingStats {
+  windows: Vec<Duration>,
+  retention: u64,
+  tickers: BTreeMap<String, TickerSeries>,
+  latest: Option<u64>,
+}
+
+impl RollingStats {
+  pub fn new(windows: Vec<Duration>) -> Self {
+    let retention = windows
+      .iter()
+      .map(|window| window.as_millis() as u64)
+      .max()
+      .unwrap_or_default();
+
+    Self {
+      windows,
+      retention,
+      tickers: BTreeMap::new(),
+      latest: None,
+    }
+  }
+  /// Add `quote`, `received_at` milliseconds since Unix epoch stand for
+  /// missing quote timestamp
+  pub fn record(&mut self, quote: &StockQuote, received_at: u64) {
+    let series = self.tickers.entry(quote.ticker.clone()).or_default();
+    let timestamp = quote.timestamp.unwrap_or(received_at);
+    self.latest = Some(self.latest.unwrap_or_default().max(timestamp));
+    series.emas.resize(self.windows.len(), None);
+
+    if let Some(price) = quote.price {
+      for (window, ema) in self.windows.iter().zip(&mut series.emas) {
+        *ema = Some(match *ema {
+          Some((average, updated_at)) => {
+            let elapsed = timestamp.saturating_sub(updated_at) as f64;
+            let alpha = 1.0 - (-elapsed / window.as_millis() as f64).exp();
+
+            (average + alpha * (price - average), timestamp)
+          }
+          None => (price, timestamp),
+        });
+      }
+    }
+
+    series.samples.push_back(Sample {
+      timestamp,
+      price: quote.price,
+      volume: quote.volume,
+    });
+    while series.samples.front().is_some_and(|sample| {
+      sample.timestamp < timestamp.saturating_sub(self.retention)
+    }) {
+      series.samples.pop_front();
+    }
+  }
+  /// Latest recorded quote time
+  pub fn latest(&self) -> Option<u64> {
+    self.latest
+  }
+  /// Statistics of every ticker and window as of the latest quote time
+  pub fn stats(&self) -> Vec<WindowStats> {
+    let Some(now) = self.latest else {
+      return Vec::new();
+    };
+
+    self
+      .tickers
+      .iter()
+      .flat_map(|(ticker, series)| {
+        self.windows.iter().enumerate().map(move |(index, window)| {
+          let since = now.saturating_sub(window.as_millis() as u64);
+          let samples: Vec<&Sample> = series
+            .samples
+            .iter()
+            .filter(|sample| sample.timestamp >= since)
+            .collect();
+          let ema = series.emas.get(index).copied().flatten();
+
+          window_stats(ticker, *window, &samples, ema.map(|(ema, _)| ema), now)
+        })
+      })
+      .collect()
+  }
+}
+
+fn window_stats(
+  ticker: &str,
+  window: Duration,
+  samples: &[&Sample],
+  ema: Option<f64>,
+  timestamp: u64,
+) -> WindowStats {
+  let prices: Vec<f64> =
+    samples.iter().filter_map(|sample| sample.price).collect();
+  let (notional, volume) = samples
+    .iter()
+    .filter_map(|sample| sample.price.zip(sample.volume))
+    .fold((0.0, 0.0), |(notional, total), (price, volume)| {
+      (
+        notional + price * f64::from(volume),
+        total + f64::from(volume),
+      )
+    });
+  let squared_returns: Vec<f64> = prices
+    .windows(2)
+    .filter(|pair| pair[0] > 0.0 && pair[1] > 0.0)
+    .map(|pair| (pair[1] / pair[0]).ln().powi(2))
+    .collect();
+
+  WindowStats {
+    ticker: ticker.to_string(),
+    window_ms: window.as_millis() as u64,
+    ticks: samples.len(),
+    vwap: (volume > 0.0).then(|| notional / volume),
+    sma: (!prices.is_empty())
+      .then(|| prices.iter().sum::<f64>() / prices.len() as f64),
+    ema,
+    min: prices.iter().copied().reduce(f64::min),
+    max: prices.iter().copied().reduce(f64::max),
+    volatility: (!squared_returns.is_empty())
+      .then(|| squared_returns.iter().sum::<f64>().sqrt() * 100.0),
+    timestamp,
+  }
+}
+
+/// # Statistics writer
+///
+/// Writes window statistics in the output format, one ticker and window per
+/// line, and flushes each batch.
+pub struct StatsWriter<W: Write> {
+  format: OutputFormat,
+  writer: W,
+  header_written: bool,
+}
+
+impl StatsWriter<Box<dyn Write + Send>> {
+  /// Writer to stdout or a created file
+  pub fn open(
+    target: &OutputTarget,
+    format: OutputFormat,
+  ) -> Result<Self, AppError> {
+    Ok(Self::new(format, open_target(target)?))
+  }
+}
+
+impl<W: Write> StatsWriter<W> {
+  pub fn new(format: OutputFormat, writer: W) -> Self {
+    Self {
+      format,
+      writer,
+      header_written: false,
+    }
+  }
+  pub fn write(&mut self, stats: &[WindowStats]) -> Result<(), AppError> {
+    if !self.header_written {
+      self.header_written = true;
+      match self.format {
+        OutputFormat::JsonLines => {}
+        OutputFormat::Csv => writeln!(self.writer, "{CSV_HEADER}")?,
+        OutputFormat::Table => writeln!(
+          self.writer,
+          "{:<10} {:>9} {:>6} {:>12} {:>12} {:>12} {:>12} {:>12} {:>10} {:>15}",
+          "TICKER",
+          "WINDOW",
+          "TICKS",
+          "VWAP",
+          "SMA",
+          "EMA",
+          "MIN",
+          "MAX",
+          "VOL %",
+          "TIMESTAMP"
+        )?,
+      }
+    }
+
+    for window in stats {
+      match self.format {
+        OutputFormat::JsonLines => {
+          serde_json::to_writer(&mut self.writer, window)
+            .context("Failed serializing window statistics")?;
+          writeln!(self.writer)?;
+        }
+        OutputFormat::Csv => writeln!(
+          self.writer,
+          "{},{},{},{},{},{},{},{},{},{}",
+          csv_field(&window.ticker),
+          window.window_ms,
+          window.ticks,
+          optional(window.vwap),
+          optional(window.sma),
+          optional(window.ema),
+          optional(window.min),
+          optional(window.max),
+          optional(window.volatility),
+          window.timestamp,
+        )?,
+        OutputFormat::Table => {
+          let price = |value: Option<f64>| {
+            value.map_or_else(|| "-".to_string(), |value| format!("{value:.4}"))
+          };
+
+          writeln!(
+            self.writer,
+            "{:<10} {:>9} {:>6} {:>12} {:>12} {:>12} {:>12} {:>12} {:>10} {:>15}",
+            window.ticker,
+            format!("{}s", window.window_ms / 1000),
+            window.ticks,
+            price(window.vwap),
+            price(window.sma),
+            price(window.ema),
+            price(window.min),
+            price(window.max),
+            price(window.volatility),
+            window.timestamp,
+          )?
+        }
+      }
+    }
+
+    self.writer.flush()?;
+
+    Ok(())
+  }
+}
+
+#[cfg(test)]
+mod tests {
+  use super::*;
+
+  fn quote(timestamp: u64, price: f64) -> StockQuote {
+    StockQuote {
+      ticker: "AAPL".to_string(),
+      price: Some(price),
+      volume: Some(100),
+      timestamp: Some(timestamp),
+    }
+  }
+
+  #[test]
+  fn ema_decays_with_elapsed_time() {
+    let mut stats = RollingStats::new(vec![Duration::from_secs(60)]);
+
+    stats.record(&quote(0, 10.0), 0);
+    // A window apart the previous average keeps 1/e of its weight
+    stats.record(&quote(60_000, 20.0), 0);
+
+    let ema = stats.stats()[0].ema.unwrap();
+    assert!((ema - (20.0 - 10.0 / std::f64::consts::E)).abs() < 1e-9);
+  }
+}
@@ -0,0 +1,126 @@
+use clap::Parser;
+use common::stock::QuoteField;
+use common::utils::{
+  path_validation, pem_path_validation, percent_validation, port_validation,
+  rate_validation, server_address_validation, window_validation,
+};
+use quote_client::{
+  alerts::AlertTarget,
+  consts,
+  output::{OutputFormat, OutputTarget},
+};
+use std::{net::SocketAddr, path::PathBuf, time::Duration};
+
+#[derive(Debug, Parser)]
+#[command(version, about, next_line_help = true)]
+pub(crate) struct CliArgs {
+  #[arg(short = 'f', long, value_name = "Tickers file", value_parser = path_validation, required_unless_present = "replay")]
+  pub tickers_file: Option<PathBuf>,
+  #[arg(short = 's',long, value_name = "Server TCP address", value_parser = server_address_validation, required_unless_present = "replay")]
+  pub server_tcp_addr: Option<SocketAddr>,
+  #[arg(short = 'S',long, value_name = "Server UDP port", value_parser = port_validation, required_unless_present = "replay")]
+  pub server_udp_port: Option<u16>,
+  #[arg(short = 'c',long, value_name = "Client UDP address", value_parser = server_address_validation, required_unless_present = "replay")]
+  pub client_udp_addr: Option<SocketAddr>,
+  /// Fail subscription when any requested ticker is unknown to server
+  #[arg(long)]
+  pub strict: bool,
+  /// Minimum interval between quote updates in milliseconds
+  #[arg(long, value_name = "Milliseconds")]
+  pub min_interval: Option<u64>,
+  /// Maximum quote updates per second
+  #[arg(long, value_name = "Updates per second", value_parser = rate_validation)]
+  pub max_rate: Option<f64>,
+  /// Receive only quotes changed since the previous update
+  #[arg(long)]
+  pub delta: bool,
+  /// Interval between full snapshots in delta mode in milliseconds
+  #[arg(long, value_name = "Milliseconds", requires = "delta")]
+  pub full_refresh: Option<u64>,
+  /// Quote fields to receive, e.g. `price,volume`, all fields when not set
+  #[arg(long, value_name = "Fields", value_delimiter = ',')]
+  pub fields: Option<Vec<QuoteField>>,
+  /// Quotes output file, `-` for stdout
+  #[arg(short = 'o', long, value_name = "Path", default_value_t = OutputTarget::Stdout)]
+  pub output: OutputTarget,
+  /// Quotes output format: `json-lines`, `csv` or `table`
+  #[arg(long, value_name = "Format", default_value_t = OutputFormat::JsonLines)]
+  pub format: OutputFormat,
+  /// Show a live dashboard of subscribed tickers instead of writing quotes
+  #[arg(long, conflicts_with_all = ["output", "format"])]
+  pub tui: bool,
+  /// Append received datagrams to a recording file for later replay
+  #[arg(long, value_name = "Path")]
+  pub record: Option<PathBuf>,
+  /// Write quotes of a recording file instead of subscribing to server
+  #[arg(long, value_name = "Path", conflicts_with_all = ["tui", "record"])]
+  pub replay: Option<PathBuf>,
+  /// Replay quotes at the recorded pace instead of as fast as possible
+  #[arg(long, requires = "replay")]
+  pub realtime: bool,
+  /// Price alert rules file, e.g. `AAPL > 200` or `volume spike 3x avg`
+  #[arg(long, value_name = "Alert rules file", value_parser = path_validation)]
+  pub alerts: Option<PathBuf>,
+  /// Alerts output file, `exec:COMMAND` hook or `-` for stdout
+  #[arg(long, value_name = "Target", default_value_t = AlertTarget::Stdout, requires = "alerts")]
+  pub alerts_output: AlertTarget,
+  /// Minimum milliseconds between alerts of a rule for a ticker
+  #[arg(long, value_name = "Milliseconds", default_value_t = consts::ALERT_COOLDOWN.as_millis() as u64, requires = "alerts")]
+  pub alert_cooldown: u64,
+  /// Percent of threshold a value moves back past before a rule alerts again
+  #[arg(long, value_name = "Percent", default_value_t = consts::ALERT_HYSTERESIS * 100.0, value_parser = percent_validation, requires = "alerts")]
+  pub alert_hysteresis: f64,
+  /// Rolling analytics windows per ticker, e.g. `1m,5m`
+  #[arg(long, value_name = "Windows", value_parser = window_validation, value_delimiter = ',')]
+  pub analytics: Option<Vec<Duration>>,
+  /// Milliseconds of quote time between analytics reports
+  #[arg(long, value_name = "Milliseconds", default_value_t = consts::ANALYTICS_INTERVAL.as_millis() as u64, requires = "analytics")]
+  pub analytics_interval: u64,
+  /// Analytics output file, `-` for stdout
+  #[arg(long, value_name = "Path", default_value_t = OutputTarget::Stdout, requires = "analytics")]
+  pub analytics_output: OutputTarget,
+  /// Analytics output format: `json-lines`, `csv` or `table`
+  #[arg(long, value_name = "Format", default_value_t = OutputFormat::JsonLines, requires = "analytics")]
+  pub analytics_format: OutputFormat,
+  /// Log latency percentiles per ticker on interval and on exit
+  #[arg(long)]
+  pub latency: bool,
+  /// Milliseconds between latency reports
+  #[arg(long, value_name = "Milliseconds", default_value_t = consts::LATENCY_REPORT_INTERVAL.as_millis() as u64, requires = "latency")]
+  pub latency_interval: u64,
+  /// Milliseconds without data or server heartbeats before feed is stale
+  #[arg(long, value_name = "Milliseconds", default_value_t = consts::STALE_FEED_TIMEOUT.as_millis() as u64)]
+  pub stale_after: u64,
+  /// Proposed heartbeat interval in milliseconds, server applies its limits
+  #[arg(long, value_name = "Milliseconds")]
+  pub heartbeat_interval: Option<u64>,
+  /// Proposed eviction timeout without heartbeats in milliseconds
+  #[arg(long, value_name = "Milliseconds")]
+  pub heartbeat_timeout: Option<u64>,
+  /// Receive quote datagrams encrypted and authenticated with the session key
+  #[arg(long)]
+  pub encrypt: bool,
+  /// Username for server authentication
+  #[arg(short = 'u', long, value_name = "Username", requires = "password")]
+  pub username: Option<String>,
+  /// Password for server authentication
+  #[arg(
+    long,
+    value_name = "Password",
+    env = "QUOTE_CLIENT_PASSWORD",
+    hide_env_values = true
+  )]
+  pub password: Option<String>,
+  /// CA certificates to verify server certificate, enables TLS
+  #[arg(long, value_name = "PEM file", value_parser = pem_path_validation)]
+  pub tls_ca: Option<PathBuf>,
+  /// Client TLS certificate chain
+  #[arg(long, value_name = "PEM file", value_parser = pem_path_validation, requires_all = ["tls_key", "tls_ca"])]
+  pub tls_cert: Option<PathBuf>,
+  /// Client TLS private key
+  #[arg(long, value_name = "PEM file", value_parser = pem_path_validation, requires = "tls_cert")]
+  pub tls_key: Option<PathBuf>,
+  /// Server name in server certificate, server IP address by default
+  #[arg(long, value_name = "Server name", requires = "tls_ca")]
+  pub tls_server_name: Option<String>,
+}
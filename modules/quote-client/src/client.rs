@@ -0,0 +1,602 @@
+use std::{
+  borrow::Cow,
+  io::{self, BufReader, Write},
+  net::{SocketAddr, TcpStream, UdpSocket},
+  sync::atomic::Ordering,
+  sync::{
+    Arc,
+    atomic::AtomicBool,
+    mpsc::{SyncSender, TrySendError},
+  },
+  thread,
+  thread::JoinHandle,
+  time::{Duration, Instant, SystemTime, UNIX_EPOCH},
+};
+
+use anyhow::{Context, anyhow};
+use serde_json::json;
+use tracing::{error, info, warn};
+
+use common::{
+  error::AppError,
+  seal::{DatagramCipher, ReplayWindow, SEALED_DATAGRAM_TAG},
+  session::{ClientDatagram, HeartbeatPolicy, SessionCredentials},
+  stock::{
+    StockDatagram, StockQuote, StockRequest, StockResponse,
+    StockResponseStatus, SubscriptionReport,
+  },
+  tls::{self, ControlStream},
+  utils::read_json,
+};
+
+use crate::{
+  configs::consts,
+  connection::{Backoff, ConnectionState, SessionHeartbeats, StreamState},
+  recording::Recorder,
+  subscription::{KnownQuotes, SubscriptionConfig},
+};
+
+/// Session credentials and heartbeat terms of accepted subscription
+type AcceptedSession = (SessionCredentials, Option<HeartbeatPolicy>);
+
+/// Control channel TLS settings
+#[derive(Debug, Clone)]
+pub struct ClientTls {
+  pub config: Arc<rustls::ClientConfig>,
+  /// Name in server certificate
+  pub server_name: String,
+}
+
+#[derive(Debug)]
+pub(crate) struct Client {
+  config: SubscriptionConfig,
+  server_udp_addr: SocketAddr,
+  stream: Arc<StreamState>,
+  udp: UdpSocket,
+  quotes: SyncSender<StockQuote>,
+  recorder: Option<Recorder>,
+  shutdown: Arc<AtomicBool>,
+}
+
+impl Client {
+  pub fn new(
+    config: SubscriptionConfig,
+    quotes: SyncSender<StockQuote>,
+    shutdown: Arc<AtomicBool>,
+  ) -> Result<Self, AppError> {
+    let mut server_udp_addr = config.server_tcp_addr;
+    server_udp_addr.set_port(config.server_udp_port);
+    let udp_socket =
+      UdpSocket::bind(config.client_udp_addr).map_err(|err| {
+        AppError::AddressBindError {
+          addr: config.client_udp_addr,
+          err,
+        }
+      })?;
+    udp_socket
+      .set_read_timeout(Some(consts::UDP_READ_TIMEOUT))
+      .map_err(|err| AppError::UdpSocketError { err })?;
+    udp_socket
+      .set_write_timeout(Some(consts::UDP_WRITE_TIMEOUT))
+      .map_err(|err| AppError::UdpSocketError { err })?;
+    let recorder = config.record.as_deref().map(Recorder::open).transpose()?;
+
+    Ok(Self {
+      config,
+      stream: Arc::new(StreamState::new()),
+      server_udp_addr,
+      udp: udp_socket,
+      quotes,
+      recorder,
+      shutdown,
+    })
+  }
+  pub fn stream(&self) -> Arc<StreamState> {
+    Arc::clone(&self.stream)
+  }
+  pub fn local_addr(&self) -> Result<SocketAddr, AppError> {
+    self
+      .udp
+      .local_addr()
+      .map_err(|err| AppError::UdpSocketError { err })
+  }
+  /// Subscribe and keep the subscription alive until shutdown or rejection
+  pub fn run(&self) -> Result<(), AppError> {
+    info!("Run client");
+
+    let udp_server = self.start_udp_server()?;
+    let mut backoff = Backoff::default();
+    let mut heartbeats = None;
+    let mut state = ConnectionState::Connecting;
+    let mut result = Ok(());
+
+    while state != ConnectionState::Closed {
+      let next = match state {
+        ConnectionState::Connecting => match self.send_stream_request() {
+          Ok((new_session, heartbeat)) => {
+            let cipher = self
+              .config
+              .options
+              .encrypt
+              .then(|| DatagramCipher::new(&new_session))
+              .transpose()?;
+            self.stream.start(cipher);
+            let interval = heartbeat
+              .map(|heartbeat| heartbeat.interval())
+              .unwrap_or(consts::HEALTH_CHECK_STREAMING_TIMEOUT);
+            info!(interval = ?interval, "Heartbeat interval:");
+            heartbeats = Some(SessionHeartbeats::new(new_session, interval));
+            backoff.reset();
+
+            ConnectionState::Subscribed
+          }
+          // Server rejected the subscription or broke the protocol, retrying
+          // won't help, unlike refusals by server limits which go through
+          // backoff
+          Err(
+            e @ (AppError::SubscriptionRejected { .. }
+            | AppError::ProtocolError { .. }),
+          ) => {
+            result = Err(e);
+            ConnectionState::Closed
+          }
+          Err(e) => {
+            warn!(err = %e, "Failed subscribing:");
+            ConnectionState::Backoff
+          }
+        },
+        ConnectionState::Subscribed | ConnectionState::Stale => {
+          match &mut heartbeats {
+            Some(heartbeats) => self.check_stream(heartbeats)?,
+            None => ConnectionState::Connecting,
+          }
+        }
+        ConnectionState::Lost => ConnectionState::Backoff,
+        ConnectionState::Backoff => {
+          let delay = backoff.next_delay();
+          info!(delay = ?delay, "Reconnect after backoff:");
+          self.wait(delay);
+
+          ConnectionState::Connecting
+        }
+        ConnectionState::Closed => ConnectionState::Closed,
+      };
+      let next = if self.shutdown.load(Ordering::Acquire) {
+        ConnectionState::Closed
+      } else {
+        next
+      };
+
+      if next == state {
+        continue;
+      }
+      if next == ConnectionState::Stale {
+        warn!(silence = ?self.stream.silence(), "Stale feed:");
+      }
+
+      info!(from = %state, to = %next, "Connection state:");
+      self.stream.set_state(next);
+      state = next;
+    }
+
+    // Stop UDP server of a rejected subscription too
+    self.shutdown.store(true, Ordering::Release);
+    udp_server.join().map_err(|_| {
+      AppError::OtherError(anyhow!("Failed waiting for udp server thread"))
+    })??;
+
+    result
+  }
+  fn start_udp_server(
+    &self,
+  ) -> Result<JoinHandle<Result<(), AppError>>, AppError> {
+    info!("Start UDP server");
+
+    let shutdown = Arc::clone(&self.shutdown);
+    let udp = self
+      .udp
+      .try_clone()
+      .map_err(|err| AppError::UdpSocketError { err })?;
+    let server_udp_addr = self.server_udp_addr;
+    let stream = Arc::clone(&self.stream);
+    let quotes = self.quotes.clone();
+    let mut sealed = self
+      .config
+      .options
+      .encrypt
+      .then(|| SealedReceiver::new(Arc::clone(&self.stream)));
+    let mut recorder = self
+      .recorder
+      .as_ref()
+      .map(Recorder::try_clone)
+      .transpose()?;
+    let latency = self.stream.latency();
+
+    Ok(thread::spawn(move || {
+      let mut buf = vec![0u8; 64 * 1024];
+      let mut last_seq = 0;
+      let mut generation = stream.generation();
+      let mut dropped = 0u64;
+      let mut known = KnownQuotes::default();
+
+      while !shutdown.load(Ordering::Acquire) {
+        match udp.recv(&mut buf) {
+          Ok(n) => {
+            let received_at = SystemTime::now();
+            // New subscription restarts sequence numbering
+            if generation != stream.generation() {
+              generation = stream.generation();
+              last_seq = 0;
+              if let Some(sealed) = &mut sealed {
+                sealed.reset();
+              }
+            }
+
+            let payload = match &mut sealed {
+              Some(sealed) => match sealed.open(&buf[..n]) {
+                Some(payload) => payload,
+                None => continue,
+              },
+              None => Cow::Borrowed(&buf[..n]),
+            };
+            let datagram =
+              match serde_json::from_slice::<StockDatagram>(&payload) {
+                Ok(datagram) => datagram,
+                Err(e) => {
+                  warn!(err = %e, "Malformed datagram");
+                  continue;
+                }
+              };
+
+            if let Some(active) = &mut recorder {
+              let sealed = matches!(payload, Cow::Owned(_)).then(|| &buf[..n]);
+
+              if let Err(e) =
+                active.record(received_at, generation, &payload, sealed)
+              {
+                warn!(err = %e, "Failed recording datagram, recording stopped:");
+                recorder = None;
+              }
+            }
+
+            if let StockDatagram::Challenge { nonce } = datagram {
+              info!("Verify address ownership");
+
+              let message =
+                json!(ClientDatagram::Verification { nonce }).to_string();
+              udp.send_to(message.as_bytes(), server_udp_addr).context(
+                format!("Failed sending to UDP {server_udp_addr:?}"),
+              )?;
+              continue;
+            }
+            // Challenges are not feed activity, anyone may send them
+            stream.touch();
+
+            let mut lost = 0;
+            if let Some(seq) = datagram.seq() {
+              if seq > last_seq + 1 {
+                lost = seq - last_seq - 1;
+                warn!(lost, "Missed stock datagrams:");
+              }
+              // Reordered datagrams don't move the sequence back
+              last_seq = last_seq.max(seq);
+            }
+            stream.record(lost);
+
+            if let StockDatagram::GoingAway { message, .. } = datagram {
+              warn!(message = %message, "Server is going away:");
+              stream.set_going_away();
+              continue;
+            }
+            if let StockDatagram::Snapshot { seq, .. } = datagram {
+              info!(seq, "Stock snapshot");
+            }
+            let new_quotes = known.new_quotes(&datagram);
+            latency.record(
+              &new_quotes,
+              datagram.sent_at(),
+              received_at
+                .duration_since(UNIX_EPOCH)
+                .unwrap_or_default()
+                .as_micros() as u64,
+            );
+            for stock_quote in new_quotes {
+              match quotes.try_send(stock_quote) {
+                Ok(()) => {}
+                Err(TrySendError::Full(_)) => dropped += 1,
+                // Subscription is dropped, nobody reads quotes anymore
+                Err(TrySendError::Disconnected(_)) => {
+                  shutdown.store(true, Ordering::Release);
+                  break;
+                }
+              }
+            }
+          }
+          // Read timeout lets the server check for shutdown
+          Err(e)
+            if [io::ErrorKind::TimedOut, io::ErrorKind::WouldBlock]
+              .contains(&e.kind()) => {}
+          Err(e) => return Err(e).context("udp_socket.recv failed")?,
+        }
+      }
+
+      if dropped > 0 {
+        warn!(dropped, "Dropped quotes, subscription read too slowly:");
+      }
+      if let Some(sealed) = sealed {
+        info!(
+          tampered = sealed.tampered,
+          replayed = sealed.replayed,
+          "Rejected datagrams:"
+        );
+      }
+      info!("Stop udp server");
+
+      Ok(())
+    }))
+  }
+  fn send_stream_request(&self) -> Result<AcceptedSession, AppError> {
+    info!("Send stream request");
+
+    let stream =
+      TcpStream::connect(self.config.server_tcp_addr).context(format!(
+        "Failed connecting to server {}",
+        self.config.server_tcp_addr
+      ))?;
+    stream
+      .set_nodelay(true)
+      .map_err(|err| AppError::TcpStreamError { err })?;
+    stream
+      .set_read_timeout(Some(consts::TCP_STREAM_READ_TIMEOUT))
+      .map_err(|err| AppError::TcpStreamError { err })?;
+    stream
+      .set_write_timeout(Some(consts::TCP_STREAM_WRITE_TIMEOUT))
+      .map_err(|err| AppError::TcpStreamError { err })?;
+
+    let peer_addr = stream
+      .peer_addr()
+      .context("Failed reading stream peer address")?;
+    let mut stream = tls::connect(
+      stream,
+      self
+        .config
+        .tls
+        .as_ref()
+        .map(|tls| (&tls.config, tls.server_name.as_str())),
+    )?;
+
+    let stock_request = StockRequest {
+      kind: "STREAM".to_string(),
+      addr: self.local_addr()?,
+      tickers: self.config.tickers.clone(),
+      options: self.config.options.clone(),
+      auth: self.config.auth.clone(),
+    };
+
+    let message = json!(stock_request).to_string();
+    stream
+      .write_all(message.as_bytes())
+      .context("Failed writing to TCP stream")?;
+    stream.flush()?;
+
+    info!("Request sent");
+
+    self.read_tcp_stream(stream, peer_addr)
+  }
+  fn read_tcp_stream(
+    &self,
+    mut stream: Box<dyn ControlStream>,
+    peer_addr: SocketAddr,
+  ) -> Result<AcceptedSession, AppError> {
+    info!(peer = %peer_addr, "Read TCP stream");
+
+    let StockResponse {
+      message,
+      status,
+      report,
+      session,
+      heartbeat,
+    } = read_json::<StockResponse>(BufReader::new(&mut stream))?;
+
+    if let Some(report) = report {
+      log_subscription_report(&report);
+    }
+
+    match status {
+      StockResponseStatus::Ok => {
+        info!(message = %message, "Request success:");
+      }
+      StockResponseStatus::Error => {
+        error!(message = %message, "Request error:");
+        return Err(AppError::SubscriptionRejected { message });
+      }
+      StockResponseStatus::Retry => {
+        warn!(message = %message, "Request refused for now:");
+        return Err(AppError::SubscriptionDeferred { message });
+      }
+    }
+
+    let session = session.ok_or_else(|| AppError::ProtocolError {
+      message: "Accepted response without session credentials".to_string(),
+    })?;
+
+    Ok((session, heartbeat))
+  }
+  /// Send heartbeat when due and check stream liveness
+  ///
+  /// Feed is stale when no datagrams arrive within the stale window. Stream is
+  /// lost when server is going away, no datagrams arrive within the loss
+  /// timeout or heartbeats can't be sent several times in a row.
+  fn check_stream(
+    &self,
+    heartbeats: &mut SessionHeartbeats,
+  ) -> Result<ConnectionState, AppError> {
+    if self.stream.is_going_away() {
+      warn!("Stream lost, server is going away");
+      return Ok(ConnectionState::Lost);
+    }
+
+    let stale_after = self.config.stale_after;
+    let silence = self.stream.silence();
+    if silence > consts::STREAM_LOSS_TIMEOUT.max(stale_after) {
+      warn!(silence = ?silence, "Stream lost, no data received:");
+      return Ok(ConnectionState::Lost);
+    }
+
+    let now = Instant::now();
+    if heartbeats.is_due(now) {
+      let message =
+        json!(ClientDatagram::Heartbeat(heartbeats.next(now)?)).to_string();
+
+      match self.udp.send_to(message.as_bytes(), self.server_udp_addr) {
+        Ok(_) => heartbeats.failures = 0,
+        Err(e) => {
+          heartbeats.failures += 1;
+          warn!(err = %e, failures = heartbeats.failures, "Failed sending heartbeat:");
+
+          if heartbeats.failures >= consts::HEARTBEAT_FAILURE_LIMIT {
+            warn!("Stream lost, heartbeats failed");
+            return Ok(ConnectionState::Lost);
+          }
+        }
+      }
+    }
+
+    thread::sleep(consts::STREAM_CHECK_INTERVAL);
+
+    Ok(if silence > stale_after {
+      ConnectionState::Stale
+    } else {
+      ConnectionState::Subscribed
+    })
+  }
+  /// Sleep for `delay` unless client shuts down earlier
+  fn wait(&self, delay: Duration) {
+    let deadline = Instant::now() + delay;
+
+    while !self.shutdown.load(Ordering::Acquire) {
+      let remaining = deadline.saturating_duration_since(Instant::now());
+      if remaining.is_zero() {
+        break;
+      }
+
+      thread::sleep(remaining.min(consts::SHUTDOWN_POLL_INTERVAL));
+    }
+  }
+}
+
+/// Receiver of sealed quote datagrams, which counts rejected datagrams
+#[derive(Debug)]
+struct SealedReceiver {
+  stream: Arc<StreamState>,
+  window: ReplayWindow,
+  // Sealed datagram of the current subscription was opened, so its address
+  // is verified and plain challenges aren't expected anymore
+  verified: bool,
+  tampered: u64,
+  replayed: u64,
+}
+
+impl SealedReceiver {
+  fn new(stream: Arc<StreamState>) -> Self {
+    Self {
+      stream,
+      window: ReplayWindow::default(),
+      verified: false,
+      tampered: 0,
+      replayed: 0,
+    }
+  }
+  /// Forget sequence numbers of the previous subscription
+  fn reset(&mut self) {
+    self.window = ReplayWindow::default();
+    self.verified = false;
+  }
+  /// Payload of authentic datagram, `None` for rejected datagrams
+  fn open<'a>(&mut self, message: &'a [u8]) -> Option<Cow<'a, [u8]>> {
+    if message.first() != Some(&SEALED_DATAGRAM_TAG) {
+      // Challenges precede the session key, so they are never sealed, and
+      // aren't sent once the address is verified
+      if let Ok(StockDatagram::Challenge { .. }) =
+        serde_json::from_slice::<StockDatagram>(message)
+      {
+        if !self.verified {
+          return Some(Cow::Borrowed(message));
+        }
+      }
+
+      self.tampered += 1;
+      warn!(tampered = self.tampered, "Rejected plain quote datagram:");
+      return None;
+    }
+
+    let opened = match self.stream.cipher().as_ref() {
+      Some(cipher) => cipher.open(message),
+      None => {
+        warn!("Sealed datagram received before session key");
+        return None;
+      }
+    };
+
+    let Ok((seq, payload)) = opened else {
+      self.tampered += 1;
+      warn!(tampered = self.tampered, "Rejected tampered datagram:");
+      return None;
+    };
+
+    if !self.window.accept(seq) {
+      self.replayed += 1;
+      warn!(seq, replayed = self.replayed, "Rejected replayed datagram:");
+      return None;
+    }
+    self.verified = true;
+
+    Some(Cow::Owned(payload))
+  }
+}
+
+fn log_subscription_report(report: &SubscriptionReport) {
+  info!(accepted = ?report.accepted, "Subscribed tickers:");
+
+  for pattern in &report.unknown {
+    match report.suggestions.get(pattern) {
+      Some(suggestions) => {
+        warn!(ticker = %pattern, suggestions = ?suggestions, "Unknown ticker:")
+      }
+      None => warn!(ticker = %pattern, "Unknown ticker:"),
+    }
+  }
+  if !report.denied.is_empty() {
+    warn!(denied = ?report.denied, "Not entitled tickers:");
+  }
+}
+
+#[cfg(test)]
+mod tests {
+  use super::*;
+
+  #[test]
+  fn plain_challenges_are_accepted_until_verified() {
+    let session = SessionCredentials::generate();
+    let stream = Arc::new(StreamState::new());
+    stream.start(Some(DatagramCipher::new(&session).unwrap()));
+    let mut receiver = SealedReceiver::new(stream);
+    let challenge = json!(StockDatagram::Challenge {
+      nonce: "nonce".to_string(),
+    })
+    .to_string();
+    let heartbeat = DatagramCipher::new(&session)
+      .unwrap()
+      .seal(1, br#"{"type":"heartbeat","seq":1}"#)
+      .unwrap();
+
+    assert!(receiver.open(challenge.as_bytes()).is_some());
+    assert!(receiver.open(&heartbeat).is_some());
+    assert!(receiver.open(challenge.as_bytes()).is_none());
+    assert_eq!(receiver.tampered, 1);
+
+    // Resubscription is verified anew
+    receiver.reset();
+    assert!(receiver.open(challenge.as_bytes()).is_some());
+  }
+}
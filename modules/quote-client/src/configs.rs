@@ -1,29 +1,31 @@
-use clap::Parser;
-use common::utils::{
-  path_validation, port_validation, server_address_validation,
-};
-use std::{net::SocketAddr, path::PathBuf};
-
-#[derive(Debug, Parser)]
-#[command(version, about, next_line_help = true)]
-pub(crate) struct CliArgs {
-  #[arg(short = 'f', long, value_name = "Tickers file", value_parser = path_validation)]
-  pub tickers_file: PathBuf,
-  #[arg(short = 's',long, value_name = "Server TCP address", value_parser = server_address_validation)]
-  pub server_tcp_addr: SocketAddr,
-  #[arg(short = 'S',long, value_name = "Server UDP port", value_parser = port_validation)]
-  pub server_udp_port: u16,
-  #[arg(short = 'c',long, value_name = "Client UDP address", value_parser = server_address_validation)]
-  pub client_udp_addr: SocketAddr,
-}
-
-pub(crate) mod consts {
+pub mod consts {
   use std::time::Duration;
 
-  pub const UDP_READ_TIMEOUT: Duration = Duration::from_secs(5);
+  pub const UDP_READ_TIMEOUT: Duration = Duration::from_millis(500);
   pub const UDP_WRITE_TIMEOUT: Duration = Duration::from_secs(5);
   pub const TCP_STREAM_READ_TIMEOUT: Duration = Duration::from_secs(2);
   pub const TCP_STREAM_WRITE_TIMEOUT: Duration = Duration::from_secs(2);
+  // Heartbeat interval of servers which don't negotiate it
   pub const HEALTH_CHECK_STREAMING_TIMEOUT: Duration =
     Duration::from_millis(50);
+  pub const STREAM_CHECK_INTERVAL: Duration = Duration::from_millis(50);
+  pub const STREAM_LOSS_TIMEOUT: Duration = Duration::from_secs(5);
+  pub const STALE_FEED_TIMEOUT: Duration = Duration::from_secs(3);
+  pub const HEARTBEAT_FAILURE_LIMIT: u32 = 3;
+  pub const RECONNECT_BACKOFF_BASE: Duration = Duration::from_millis(500);
+  pub const RECONNECT_BACKOFF_MAX: Duration = Duration::from_secs(30);
+  pub const SHUTDOWN_POLL_INTERVAL: Duration = Duration::from_millis(100);
+  // Received quotes not yet read from subscription, newer quotes are dropped
+  pub const QUOTES_BUFFER_CAPACITY: usize = 4096;
+  pub const ALERT_COOLDOWN: Duration = Duration::from_secs(60);
+  // Fraction of threshold, e.g. `AAPL > 200` alerts again below 198
+  pub const ALERT_HYSTERESIS: f64 = 0.01;
+  // Alert hooks running at once, alerts over the limit skip the hook
+  pub const ALERT_MAX_HOOKS: usize = 16;
+  // Running alert hooks are waited for on exit, then killed
+  pub const ALERT_HOOK_EXIT_TIMEOUT: Duration = Duration::from_secs(5);
+  // Average volume window of `volume spike` rules without explicit window
+  pub const ALERT_AVERAGE_WINDOW: Duration = Duration::from_secs(5 * 60);
+  pub const ANALYTICS_INTERVAL: Duration = Duration::from_secs(5);
+  pub const LATENCY_REPORT_INTERVAL: Duration = Duration::from_secs(10);
 }
@@ -0,0 +1,195 @@
+use std::{
+  fmt,
+  sync::{
+    Mutex, RwLock, RwLockReadGuard,
+    atomic::{AtomicBool, AtomicU64, Ordering},
+  },
+  time::{Duration, Instant},
+};
+
+use common::{
+  error::AppError,
+  seal::DatagramCipher,
+  session::{Heartbeat, SessionCredentials},
+};
+
+use crate::{configs::consts, latency::LatencyStats};
+
+/// Control connection state, transitions are logged by client
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum ConnectionState {
+  Connecting,
+  Subscribed,
+  /// Neither data nor heartbeats arrive from server within the stale window
+  Stale,
+  Lost,
+  Backoff,
+  Closed,
+}
+
+impl fmt::Display for ConnectionState {
+  fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+    let state = match self {
+      Self::Connecting => "connecting",
+      Self::Subscribed => "subscribed",
+      Self::Stale => "stale",
+      Self::Lost => "lost",
+      Self::Backoff => "backoff",
+      Self::Closed => "closed",
+    };
+
+    f.write_str(state)
+  }
+}
+
+/// Feed status of a subscription
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub struct FeedStatus {
+  pub state: ConnectionState,
+  /// Authentic datagrams received from server, challenges excluded
+  pub datagrams: u64,
+  /// Datagrams missed in sequence gaps
+  pub lost: u64,
+  /// Time since the last authentic datagram
+  pub silence: Duration,
+}
+
+/// Exponential reconnect backoff with jitter
+///
+/// Delay doubles with each attempt up to the maximum, and a random half of it
+/// is dropped, so clients of a restarted server don't reconnect in lockstep.
+#[derive(Debug, Default)]
+pub(crate) struct Backoff {
+  attempt: u32,
+}
+
+impl Backoff {
+  pub fn next_delay(&mut self) -> Duration {
+    let delay = consts::RECONNECT_BACKOFF_BASE
+      .saturating_mul(1 << self.attempt.min(16))
+      .min(consts::RECONNECT_BACKOFF_MAX);
+    self.attempt += 1;
+
+    delay / 2 + (delay / 2).mul_f64(rand::random::<f64>())
+  }
+  pub fn reset(&mut self) {
+    self.attempt = 0;
+  }
+}
+
+/// Signed heartbeats of the current subscription, paced by negotiated
+/// heartbeat interval
+#[derive(Debug)]
+pub(crate) struct SessionHeartbeats {
+  session: SessionCredentials,
+  interval: Duration,
+  last_sent: Option<Instant>,
+  counter: u64,
+  /// Heartbeats failed to be sent in a row
+  pub failures: u32,
+}
+
+impl SessionHeartbeats {
+  pub fn new(session: SessionCredentials, interval: Duration) -> Self {
+    Self {
+      session,
+      interval,
+      last_sent: None,
+      counter: 0,
+      failures: 0,
+    }
+  }
+  pub fn is_due(&self, now: Instant) -> bool {
+    self
+      .last_sent
+      .is_none_or(|last_sent| now >= last_sent + self.interval)
+  }
+  pub fn next(&mut self, now: Instant) -> Result<Heartbeat, AppError> {
+    self.last_sent = Some(now);
+    self.counter += 1;
+
+    Heartbeat::sign(&self.session, self.counter)
+  }
+}
+
+/// Current subscription stream, shared by control loop and UDP server
+#[derive(Debug)]
+pub(crate) struct StreamState {
+  // Bumped on each accepted subscription, which restarts `seq` numbering
+  generation: AtomicU64,
+  cipher: RwLock<Option<DatagramCipher>>,
+  last_datagram: Mutex<Instant>,
+  going_away: AtomicBool,
+  state: Mutex<ConnectionState>,
+  datagrams: AtomicU64,
+  lost: AtomicU64,
+  latency: LatencyStats,
+}
+
+impl StreamState {
+  pub fn new() -> Self {
+    Self {
+      generation: AtomicU64::new(0),
+      cipher: RwLock::new(None),
+      last_datagram: Mutex::new(Instant::now()),
+      going_away: AtomicBool::new(false),
+      state: Mutex::new(ConnectionState::Connecting),
+      datagrams: AtomicU64::new(0),
+      lost: AtomicU64::new(0),
+      latency: LatencyStats::default(),
+    }
+  }
+  /// Reset stream for a new subscription
+  pub fn start(&self, cipher: Option<DatagramCipher>) {
+    *self.cipher.write().unwrap_or_else(|err| err.into_inner()) = cipher;
+    self.touch();
+    self.going_away.store(false, Ordering::Release);
+    self.generation.fetch_add(1, Ordering::AcqRel);
+  }
+  pub fn generation(&self) -> u64 {
+    self.generation.load(Ordering::Acquire)
+  }
+  pub fn cipher(&self) -> RwLockReadGuard<'_, Option<DatagramCipher>> {
+    self.cipher.read().unwrap_or_else(|err| err.into_inner())
+  }
+  /// Record an authentic datagram from server
+  pub fn touch(&self) {
+    *self
+      .last_datagram
+      .lock()
+      .unwrap_or_else(|err| err.into_inner()) = Instant::now();
+  }
+  /// Time since the last authentic datagram
+  pub fn silence(&self) -> Duration {
+    self
+      .last_datagram
+      .lock()
+      .unwrap_or_else(|err| err.into_inner())
+      .elapsed()
+  }
+  pub fn set_going_away(&self) {
+    self.going_away.store(true, Ordering::Release);
+  }
+  pub fn is_going_away(&self) -> bool {
+    self.going_away.load(Ordering::Acquire)
+  }
+  pub fn set_state(&self, state: ConnectionState) {
+    *self.state.lock().unwrap_or_else(|err| err.into_inner()) = state;
+  }
+  /// Count a received datagram, which follows `lost` missed ones
+  pub fn record(&self, lost: u64) {
+    self.datagrams.fetch_add(1, Ordering::AcqRel);
+    self.lost.fetch_add(lost, Ordering::AcqRel);
+  }
+  pub fn latency(&self) -> LatencyStats {
+    self.latency.clone()
+  }
+  pub fn status(&self) -> FeedStatus {
+    FeedStatus {
+      state: *self.state.lock().unwrap_or_else(|err| err.into_inner()),
+      datagrams: self.datagrams.load(Ordering::Acquire),
+      lost: self.lost.load(Ordering::Acquire),
+      silence: self.silence(),
+    }
+  }
+}
@@ -0,0 +1,132 @@
+use std::{io::Write, path::Path, time::Duration};
+
+use tracing::info;
+
+use common::{error::AppError, stock::StockQuote, utils::unix_millis};
+use quote_client::{
+  alerts::{
+    AlertMonitor, AlertOptions, AlertTarget, AlertWriter, read_alert_rules,
+  },
+  analytics::{RollingStats, StatsWriter},
+  output::{OutputFormat, OutputTarget},
+};
+
+/// Alert rules and analytics fed with every written or displayed quote
+#[derive(Default)]
+pub(crate) struct Consumers {
+  pub alerts: Option<Alerts>,
+  pub analytics: Option<Analytics>,
+}
+
+impl Consumers {
+  pub(crate) fn consume(&mut self, quote: &StockQuote) -> Result<(), AppError> {
+    let received_at = unix_millis();
+
+    if let Some(alerts) = &mut self.alerts {
+      alerts.check(quote, received_at)?;
+    }
+    if let Some(analytics) = &mut self.analytics {
+      analytics.record(quote, received_at)?;
+    }
+
+    Ok(())
+  }
+  /// Emit the final statistics once quotes end
+  pub(crate) fn finish(&mut self) -> Result<(), AppError> {
+    match &mut self.analytics {
+      Some(analytics) => analytics.emit(),
+      None => Ok(()),
+    }
+  }
+}
+
+pub(crate) struct Alerts {
+  monitor: AlertMonitor,
+  writer: AlertWriter,
+}
+
+impl Alerts {
+  pub(crate) fn open(
+    rules: &Path,
+    target: AlertTarget,
+    options: AlertOptions,
+  ) -> Result<Self, AppError> {
+    let rules = read_alert_rules(rules)?;
+    info!(rules = rules.len(), target = %target, "Check alerts:");
+
+    Ok(Self {
+      monitor: AlertMonitor::new(rules, options),
+      writer: AlertWriter::open(target)?,
+    })
+  }
+  fn check(
+    &mut self,
+    quote: &StockQuote,
+    received_at: u64,
+  ) -> Result<(), AppError> {
+    for alert in self.monitor.evaluate(quote, received_at) {
+      info!(alert = %alert, "Alert:");
+      self.writer.write(&alert)?;
+    }
+
+    Ok(())
+  }
+}
+
+/// Rolling statistics emitted on interval of quote time
+pub(crate) struct Analytics {
+  stats: RollingStats,
+  writer: StatsWriter<Box<dyn Write + Send>>,
+  interval: Duration,
+  next_emit: Option<u64>,
+}
+
+impl Analytics {
+  pub(crate) fn open(
+    windows: Vec<Duration>,
+    interval: Duration,
+    target: &OutputTarget,
+    format: OutputFormat,
+  ) -> Result<Self, AppError> {
+    info!(
+      windows = ?windows,
+      interval = ?interval,
+      output = %target,
+      format = %format,
+      "Write analytics:"
+    );
+
+    Ok(Self {
+      stats: RollingStats::new(windows),
+      writer: StatsWriter::open(target, format)?,
+      interval,
+      next_emit: None,
+    })
+  }
+  fn record(
+    &mut self,
+    quote: &StockQuote,
+    received_at: u64,
+  ) -> Result<(), AppError> {
+    self.stats.record(quote, received_at);
+
+    let Some(latest) = self.stats.latest() else {
+      return Ok(());
+    };
+    let interval = self.interval.as_millis() as u64;
+
+    match self.next_emit {
+      Some(next_emit) if latest < next_emit => {}
+      Some(_) => {
+        self.emit()?;
+        self.next_emit = Some(latest + interval);
+      }
+      None => self.next_emit = Some(latest + interval),
+    }
+
+    Ok(())
+  }
+  fn emit(&mut self) -> Result<(), AppError> {
+    self.writer.write(&self.stats.stats())
+  }
+}
@@ -0,0 +1,271 @@
+use std::{
+  collections::BTreeMap,
+  sync::{Arc, Mutex},
+  time::Duration,
+};
+
+use common::stock::StockQuote;
+
+// Linear buckets per power of two, so percentiles are within 1/16 of value
+const SUB_BUCKETS_BITS: u32 = 4;
+const SUB_BUCKETS: u64 = 1 << SUB_BUCKETS_BITS;
+
+/// # Latency histogram
+///
+/// Log-linear histogram of microseconds: values below 16 are exact, larger
+/// values fall in 16 buckets per power of two, so percentiles overestimate by
+/// at most 1/16. Maximum is exact.
+///
+/// ```
+/// use quote_client::latency::Histogram;
+///
+/// let mut histogram = Histogram::default();
+/// for value in 1..=100 {
+///   histogram.record(value * 1000);
+/// }
+///
+/// assert_eq!(histogram.count(), 100);
+/// assert_eq!(histogram.max(), 100_000);
+/// assert!((50_000..=53_125).contains(&histogram.percentile(0.5)));
+/// assert!((99_000..=100_000).contains(&histogram.percentile(0.99)));
+/// ```
+#[derive(Debug, Clone, Default)]
+pub struct Histogram {
+  buckets: Vec<u64>,
+  count: u64,
+  max: u64,
+}
+
+impl Histogram {
+  pub fn record(&mut self, value: u64) {
+    let bucket = bucket_of(value);
+    if bucket >= self.buckets.len() {
+      self.buckets.resize(bucket + 1, 0);
+    }
+
+    self.buckets[bucket] += 1;
+    self.count += 1;
+    self.max = self.max.max(value);
+  }
+  /// Add values recorded by `other`
+  pub fn merge(&mut self, other: &Histogram) {
+    if other.buckets.len() > self.buckets.len() {
+      self.buckets.resize(other.buckets.len(), 0);
+    }
+
+    for (bucket, count) in self.buckets.iter_mut().zip(&other.buckets) {
+      *bucket += count;
+    }
+    self.count += other.count;
+    self.max = self.max.max(other.max);
+  }
+  pub fn count(&self) -> u64 {
+    self.count
+  }
+  pub fn max(&self) -> u64 {
+    self.max
+  }
+  /// Upper bound of the `quantile` value, `0` when empty
+  pub fn percentile(&self, quantile: f64) -> u64 {
+    let rank = ((self.count as f64 * quantile).ceil() as u64).max(1);
+    let mut seen = 0;
+
+    for (bucket, count) in self.buckets.iter().enumerate() {
+      seen += count;
+      if seen >= rank {
+        return bucket_high(bucket).min(self.max);
+      }
+    }
+
+    self.max
+  }
+}
+
+fn bucket_of(value: u64) -> usize {
+  if value < SUB_BUCKETS {
+    return value as usize;
+  }
+
+  let power = 63 - value.leading_zeros();
+  let shift = power - SUB_BUCKETS_BITS;
+  let sub_bucket = (value >> shift) & (SUB_BUCKETS - 1);
+
+  (((shift + 1) as u64) << SUB_BUCKETS_BITS | sub_bucket) as usize
+}
+
+/// Highest value of `bucket`
+fn bucket_high(bucket: usize) -> u64 {
+  let bucket = bucket as u64;
+  if bucket < SUB_BUCKETS {
+    return bucket;
+  }
+
+  let shift = (bucket >> SUB_BUCKETS_BITS) - 1;
+  let sub_bucket = bucket & (SUB_BUCKETS - 1);
+
+  ((SUB_BUCKETS + sub_bucket + 1) << shift) - 1
+}
+
+#[derive(Debug, Clone, Default)]
+struct Latencies {
+  /// Receive time minus quote timestamp
+  end_to_end: Histogram,
+  /// Receive time minus datagram send time
+  network: Histogram,
+}
+
+impl Latencies {
+  fn summary(&self, ticker: Option<String>) -> LatencySummary {
+    let network = self.network.count() > 0;
+
+    LatencySummary {
+      ticker,
+      count: self.end_to_end.count(),
+      p50: micros(self.end_to_end.percentile(0.5)),
+      p99: micros(self.end_to_end.percentile(0.99)),
+      max: micros(self.end_to_end.max()),
+      network_p50: network.then(|| micros(self.network.percentile(0.5))),
+      network_p99: network.then(|| micros(self.network.percentile(0.99))),
+      network_max: network.then(|| micros(self.network.max())),
+    }
+  }
+}
+
+fn micros(value: u64) -> Duration {
+  Duration::from_micros(value)
+}
+
+/// End-to-end and network latency percentiles of a ticker or every ticker
+#[derive(Debug, Clone, PartialEq)]
+pub struct LatencySummary {
+  /// Ticker, every ticker when not set
+  pub ticker: Option<String>,
+  pub count: u64,
+  pub p50: Duration,
+  pub p99: Duration,
+  pub max: Duration,
+  /// Network latency, when server sends datagram send time
+  pub network_p50: Option<Duration>,
+  pub network_p99: Option<Duration>,
+  pub network_max: Option<Duration>,
+}
+
+#[derive(Debug, Default)]
+struct Stats {
+  overall: Latencies,
+  tickers: BTreeMap<String, Latencies>,
+}
+
+/// # Latency statistics
+///
+/// Latency of quotes per ticker and overall, shared by the receiving thread
+/// and reporters. End-to-end latency is receive time minus quote timestamp,
+/// network latency is receive time minus datagram `sent_at`. Both server
+/// times are milliseconds, so latencies are up to 1ms longer, and clock skew
+/// between hosts shifts them; negative latencies count as zero.
+#[derive(Debug, Clone, Default)]
+pub struct LatencyStats {
+  stats: Arc<Mutex<Stats>>,
+}
+
+impl LatencyStats {
+  /// Record latencies of `quotes` of a datagram, `received_at_us` is receive
+  /// time in microseconds since Unix epoch. Overall network latency is
+  /// recorded once per datagram, and once per ticker of its quotes.
+  pub fn record(
+    &self,
+    quotes: &[StockQuote],
+    sent_at: Option<u64>,
+    received_at_us: u64,
+  ) {
+    let network =
+      sent_at.map(|sent_at| received_at_us.saturating_sub(sent_at * 1000));
+    let mut stats = self.stats.lock().unwrap_or_else(|err| err.into_inner());
+    let Stats { overall, tickers } = &mut *stats;
+
+    if let Some(network) = network {
+      overall.network.record(network);
+    }
+    for quote in quotes {
+      let Some(timestamp) = quote.timestamp else {
+        continue;
+      };
+      let end_to_end = received_at_us.saturating_sub(timestamp * 1000);
+
+      overall.end_to_end.record(end_to_end);
+      let ticker = tickers.entry(quote.ticker.clone()).or_default();
+      ticker.end_to_end.record(end_to_end);
+      if let Some(network) = network {
+        ticker.network.record(network);
+      }
+    }
+  }
+  /// Overall summary followed by tickers in alphabetical order, empty until
+  /// quotes with timestamps arrive
+  pub fn summary(&self) -> Vec<LatencySummary> {
+    let stats = self.stats.lock().unwrap_or_else(|err| err.into_inner());
+    if stats.overall.end_to_end.count() == 0 {
+      return Vec::new();
+    }
+
+    std::iter::once(stats.overall.summary(None))
+      .chain(
+        stats
+          .tickers
+          .iter()
+          .map(|(ticker, latencies)| latencies.summary(Some(ticker.clone()))),
+      )
+      .collect()
+  }
+}
+
+#[cfg(test)]
+mod tests {
+  use super::*;
+
+  fn quote(ticker: &str, timestamp: u64) -> StockQuote {
+    StockQuote {
+      ticker: ticker.to_string(),
+      price: Some(1.0),
+      volume: Some(100),
+      timestamp: Some(timestamp),
+    }
+  }
+
+  #[test]
+  fn network_latency_is_recorded_per_datagram() {
+    let latency = LatencyStats::default();
+
+    latency.record(&[quote("AAPL", 1)], Some(1), 2000);
+    latency.record(
+      &[quote("AAPL", 5), quote("GOOGL", 5), quote("MSFT", 5)],
+      Some(5),
+      9000,
+    );
+
+    let summary = latency.summary();
+    assert_eq!(summary[0].count, 4);
+    assert_eq!(summary[0].network_max, Some(Duration::from_millis(4)));
+    // Halfway between the datagrams, not weighted by their quotes
+    assert!(summary[0].network_p50 < Some(Duration::from_millis(2)));
+    assert_eq!(summary[1].ticker.as_deref(), Some("AAPL"));
+    assert_eq!(summary[2].network_max, Some(Duration::from_millis(4)));
+  }
+
+  #[test]
+  fn histogram_buckets_cover_their_edges() {
+    for value in [0, 15, 16, 31, 32, 33, 34, 1000, 1 << 40] {
+      let bucket = bucket_of(value);
+      let high = bucket_high(bucket);
+
+      assert!(value <= high, "{value} above bucket high {high}");
+      assert_eq!(bucket_of(high), bucket);
+      assert_eq!(bucket_of(high + 1), bucket + 1);
+    }
+
+    // Values below 16 are exact, then buckets widen per power of two
+    assert_eq!(bucket_high(bucket_of(15)), 15);
+    assert_eq!(bucket_high(bucket_of(16)), 16);
+    assert_eq!(bucket_high(bucket_of(32)), 33);
+  }
+}
@@ -0,0 +1,20 @@
+//! This is the stock quote client crate, which subscribes to quote server and
+//! receives streamed stock quotes.
+//!
+//! [`Subscription`] handles the handshake, heartbeats, reconnection and
+//! shutdown, and yields received [`StockQuote`](common::stock::StockQuote)s.
+
+pub mod alerts;
+pub mod analytics;
+mod client;
+pub mod configs;
+mod connection;
+pub mod latency;
+pub mod output;
+pub mod recording;
+mod subscription;
+
+pub use client::ClientTls;
+pub use configs::consts;
+pub use connection::{ConnectionState, FeedStatus};
+pub use subscription::{KnownQuotes, Subscription, SubscriptionConfig};
@@ -1,268 +1,306 @@
 use std::{
-  io::{self, Read, Write},
-  net::{SocketAddr, TcpStream, UdpSocket},
-  path::PathBuf,
-  sync::atomic::Ordering,
+  io::{self, ErrorKind, IsTerminal, Write},
+  path::Path,
   sync::{Arc, atomic::AtomicBool},
   thread,
-  thread::JoinHandle,
+  time::{Duration, Instant},
 };
 
-use anyhow::{Context, anyhow};
 use clap::Parser;
-use serde_json::json;
-use signal_hook::{consts::SIGTERM, low_level::raise};
-use tracing::{error, info, warn};
+use tracing::{info, warn};
 
 use common::{
   error::AppError,
-  stock::{StockQuote, StockRequest, StockResponse, StockResponseStatus},
+  stock::{Credentials, StockQuote, SubscriptionOptions},
+  tls,
   utils::{read_tickers, register_signal_hooks},
 };
+use quote_client::{
+  ClientTls, KnownQuotes, Subscription, SubscriptionConfig,
+  alerts::{AlertOptions, AlertTarget},
+  latency::LatencyStats,
+  output::{OutputFormat, OutputTarget, QuoteWriter},
+  recording::Replay,
+};
 
-mod configs;
+mod cli;
+mod consumers;
+mod reports;
+mod tui;
 
-use configs::{CliArgs, consts};
+use cli::CliArgs;
+use consumers::{Alerts, Analytics, Consumers};
+use reports::{LatencyReporter, log_latency};
 
 fn main() -> Result<(), AppError> {
+  let cli = CliArgs::parse();
+
+  // Logs are kept apart from quotes written to stdout, and are dropped when
+  // they would be drawn over the dashboard
+  let log_to_stderr = !cli.tui || !io::stderr().is_terminal();
   tracing_subscriber::fmt()
+    .with_writer(move || -> Box<dyn Write> {
+      if log_to_stderr {
+        Box::new(io::stderr())
+      } else {
+        Box::new(io::sink())
+      }
+    })
     .with_line_number(true)
     .with_thread_ids(true)
     .init();
 
   info!("Start client");
 
-  let cli = CliArgs::parse();
   let CliArgs {
     client_udp_addr,
     server_tcp_addr,
     server_udp_port,
     tickers_file,
+    strict,
+    min_interval,
+    max_rate,
+    delta,
+    full_refresh,
+    fields,
+    output,
+    format,
+    tui,
+    record,
+    replay,
+    realtime,
+    alerts,
+    alerts_output,
+    alert_cooldown,
+    alert_hysteresis,
+    analytics,
+    analytics_interval,
+    analytics_output,
+    analytics_format,
+    latency,
+    latency_interval,
+    stale_after,
+    heartbeat_interval,
+    heartbeat_timeout,
+    encrypt,
+    username,
+    password,
+    tls_ca,
+    tls_cert,
+    tls_key,
+    tls_server_name,
   } = cli;
 
-  let tickers: Vec<String> = read_tickers(PathBuf::from(tickers_file))?;
+  if tui
+    && (alerts.is_some() && alerts_output == AlertTarget::Stdout
+      || analytics.is_some() && analytics_output == OutputTarget::Stdout)
+  {
+    return Err(AppError::OtherError(anyhow::anyhow!(
+      "Alerts and analytics would be drawn over the dashboard, set \
+       `--alerts-output` and `--analytics-output`"
+    )));
+  }
+  let mut consumers = Consumers {
+    alerts: alerts
+      .map(|rules| {
+        Alerts::open(
+          &rules,
+          alerts_output,
+          AlertOptions {
+            cooldown: Duration::from_millis(alert_cooldown),
+            hysteresis: alert_hysteresis / 100.0,
+          },
+        )
+      })
+      .transpose()?,
+    analytics: analytics
+      .map(|windows| {
+        Analytics::open(
+          windows,
+          Duration::from_millis(analytics_interval),
+          &analytics_output,
+          analytics_format,
+        )
+      })
+      .transpose()?,
+  };
+
+  if let Some(recording) = replay {
+    info!(recording = ?recording, realtime, "Replay recording:");
+    let writer = open_writer(&output, format)?;
+    let stats = LatencyStats::default();
+
+    let result = write_quotes(
+      replay_quotes(&recording, realtime, stats.clone())?,
+      writer,
+      &mut consumers,
+    )
+    .and_then(|()| consumers.finish());
+    if latency {
+      log_latency(&stats);
+    }
 
-  let shutdown = Arc::new(AtomicBool::new(false));
-  register_signal_hooks(&shutdown)?;
+    return result;
+  }
 
-  let client = Client::new(
-    client_udp_addr,
+  let (
+    Some(tickers_file),
+    Some(server_tcp_addr),
+    Some(server_udp_port),
+    Some(client_udp_addr),
+  ) = (
+    tickers_file,
     server_tcp_addr,
     server_udp_port,
-    tickers,
-    shutdown,
-  )?;
-
-  info!(
-    server = %server_tcp_addr,
-    server_udp = %server_udp_port,
-    client_udp = %client_udp_addr,
-    "Initialized client"
-  );
-
-  client.run()?;
-
-  Ok(())
-}
-
-#[derive(Debug)]
-struct Client {
-  server_tcp_addr: SocketAddr,
-  server_udp_addr: SocketAddr,
-  tickers: Vec<String>,
-  udp: UdpSocket,
-  shutdown: Arc<AtomicBool>,
-}
-
-impl Client {
-  fn new(
-    client_udp_addr: SocketAddr,
-    server_tcp_addr: SocketAddr,
-    server_udp_port: u16,
-    tickers: Vec<String>,
-    shutdown: Arc<AtomicBool>,
-  ) -> Result<Self, AppError> {
-    let mut server_udp_addr = server_tcp_addr.clone();
-    server_udp_addr.set_port(server_udp_port);
-    let udp_socket = UdpSocket::bind(client_udp_addr).map_err(|err| {
-      AppError::AddressBindError {
-        addr: client_udp_addr,
-        err,
-      }
-    })?;
-    udp_socket
-      .set_read_timeout(Some(consts::UDP_READ_TIMEOUT))
-      .map_err(|err| AppError::UdpSocketError { err })?;
-
-    Ok(Self {
-      tickers,
-      server_tcp_addr,
-      server_udp_addr,
-      udp: udp_socket,
-      shutdown,
+    client_udp_addr,
+  )
+  else {
+    unreachable!("Server arguments are required unless replaying");
+  };
+
+  let auth = username
+    .zip(password)
+    .map(|(username, password)| Credentials { username, password });
+
+  let tickers: Vec<String> = read_tickers(tickers_file)?;
+  let tls = tls_ca
+    .map(|ca| -> Result<ClientTls, AppError> {
+      let identity = tls_cert.as_deref().zip(tls_key.as_deref());
+
+      Ok(ClientTls {
+        config: tls::client_config(&ca, identity)?,
+        server_name: tls_server_name
+          .unwrap_or_else(|| server_tcp_addr.ip().to_string()),
+      })
     })
-  }
-  fn run(&self) -> Result<(), AppError> {
-    info!("Run client");
-
-    let udp_server = self.start_udp_server()?;
-    let healthcheck = self.start_healthcheck_streaming()?;
-    self.send_stream_request()?;
-
-    let _ = healthcheck.join().map_err(|_| {
-      AppError::OtherError(anyhow!("Failed waiting on healthcheck_thread"))
-    })?;
-    let _ = udp_server.join().map_err(|_| {
-      AppError::OtherError(anyhow!("Failed waiting for udp server thread"))
-    })?;
+    .transpose()?;
 
-    Ok(())
+  let writer = (!tui).then(|| open_writer(&output, format)).transpose()?;
+  if let Some(record) = &record {
+    info!(recording = ?record, "Record received datagrams:");
   }
-  fn start_udp_server(
-    &self,
-  ) -> Result<JoinHandle<Result<(), AppError>>, AppError> {
-    info!("Start UDP server");
 
-    let shutdown = Arc::clone(&self.shutdown);
-    let udp = self
-      .udp
-      .try_clone()
-      .map_err(|err| AppError::UdpSocketError { err })?;
-
-    Ok(thread::spawn(move || {
-      let mut buf = vec![0u8; 4 * 1024];
-
-      while !shutdown.load(Ordering::Acquire) {
-        match udp.recv(&mut buf) {
-          Ok(n) => {
-            let stock_quotes: Vec<StockQuote> =
-              serde_json::from_slice::<Vec<StockQuote>>(&buf[..n])
-                .map_err(|err| AppError::DeserializationError { err })?;
-
-            for stock_quote in stock_quotes {
-              info!("Stock data: {stock_quote:?}");
-            }
-          }
-          Err(e)
-            if [io::ErrorKind::TimedOut, io::ErrorKind::WouldBlock]
-              .contains(&e.kind()) =>
-          {
-            warn!(err = %e, "Failed reading from UDP: ");
-          }
-          Err(e) => return Err(e).context("udp_socket.recv failed")?,
-        }
-      }
-
-      info!("Stop udp server");
+  let shutdown = Arc::new(AtomicBool::new(false));
+  register_signal_hooks(&shutdown)?;
 
-      Ok(())
-    }))
+  let subscription = Subscription::start(
+    SubscriptionConfig {
+      options: SubscriptionOptions {
+        strict,
+        min_interval_ms: min_interval,
+        max_rate,
+        delta,
+        full_refresh_ms: full_refresh,
+        fields,
+        encrypt,
+        heartbeat_interval_ms: heartbeat_interval,
+        heartbeat_timeout_ms: heartbeat_timeout,
+      },
+      auth,
+      tls,
+      stale_after: Duration::from_millis(stale_after),
+      record,
+      ..SubscriptionConfig::new(
+        server_tcp_addr,
+        server_udp_port,
+        client_udp_addr,
+        tickers,
+      )
+    },
+    Arc::clone(&shutdown),
+  )?;
+  let reporter = latency.then(|| {
+    LatencyReporter::spawn(
+      subscription.latency(),
+      Duration::from_millis(latency_interval),
+      Arc::clone(&shutdown),
+    )
+  });
+
+  let result = match writer {
+    Some(writer) => {
+      write_quotes(subscription.quotes().map(Ok), writer, &mut consumers)
+    }
+    None => tui::run(&subscription, &shutdown, &mut consumers),
+  };
+  subscription.shutdown();
+  if let Some(reporter) = reporter {
+    reporter.finish();
   }
-  fn send_stream_request(&self) -> Result<(), AppError> {
-    info!("Send stream request");
-
-    let stream = TcpStream::connect(&self.server_tcp_addr).context(format!(
-      "Failed connecting to server {}",
-      self.server_tcp_addr
-    ))?;
-    stream
-      .set_nodelay(true)
-      .map_err(|err| AppError::TcpStreamError { err })?;
-    stream
-      .set_read_timeout(Some(consts::TCP_STREAM_READ_TIMEOUT))
-      .map_err(|err| AppError::TcpStreamError { err })?;
-    stream
-      .set_write_timeout(Some(consts::TCP_STREAM_WRITE_TIMEOUT))
-      .map_err(|err| AppError::TcpStreamError { err })?;
-
-    let reader = stream
-      .try_clone()
-      .map_err(|err| AppError::TcpStreamError { err })?;
-    let mut writer = stream
-      .try_clone()
-      .map_err(|err| AppError::TcpStreamError { err })?;
-
-    let addr = self
-      .udp
-      .local_addr()
-      .context("Failed reading local address")?;
-
-    let stock_request = StockRequest {
-      kind: "STREAM".to_string(),
-      addr,
-      tickers: self.tickers.clone(),
-    };
-
-    let message = json!(stock_request).to_string();
-    writer
-      .write_all(message.as_bytes())
-      .context("Failed writing to TCP stream")?;
-    writer.flush()?;
 
-    info!("Request sent");
-
-    let _ = self.read_tcp_stream(reader);
-
-    Ok(())
-  }
-  fn read_tcp_stream(&self, mut stream: TcpStream) -> Result<(), AppError> {
-    let peer_addr = stream
-      .peer_addr()
-      .context("Failed reading stream peer address")?;
-    info!(peer = %peer_addr, "Read TCP stream");
+  result
+    .and(subscription.join())
+    .and_then(|()| consumers.finish())
+}
 
-    let mut buf = vec![0u8; 1024];
-    let n = stream.read(&mut buf)?;
+fn open_writer(
+  output: &OutputTarget,
+  format: OutputFormat,
+) -> Result<QuoteWriter<Box<dyn Write + Send>>, AppError> {
+  info!(output = %output, format = %format, "Write quotes:");
 
-    let StockResponse { message, status } =
-      serde_json::from_slice::<StockResponse>(&buf[..n])
-        .map_err(|err| AppError::DeserializationError { err })?;
+  QuoteWriter::open(output, format)
+}
 
-    match status {
-      StockResponseStatus::Ok => {
-        info!(message = %message, "Request success:");
-      }
-      StockResponseStatus::Error => {
-        error!(message = %message, "Request error:");
-        raise(SIGTERM).context("Failed raising SIGTERM signal")?;
+/// Write quotes until they end, fail or output is closed
+fn write_quotes(
+  quotes: impl IntoIterator<Item = Result<StockQuote, AppError>>,
+  mut writer: QuoteWriter<impl Write>,
+  consumers: &mut Consumers,
+) -> Result<(), AppError> {
+  for stock_quote in quotes {
+    let stock_quote = stock_quote?;
+    let written = writer
+      .write(&stock_quote)
+      .and_then(|()| consumers.consume(&stock_quote));
+
+    match written {
+      Ok(()) => {}
+      // Reader of piped output is gone, e.g. `head`
+      Err(AppError::Io(err)) if err.kind() == ErrorKind::BrokenPipe => {
+        warn!("Output is closed");
+        break;
       }
+      Err(e) => return Err(e),
     }
-
-    Ok(())
   }
-  fn start_healthcheck_streaming(
-    &self,
-  ) -> Result<JoinHandle<Result<(), AppError>>, AppError> {
-    info!("Start healthcheck streaming");
 
-    let local_addr = self
-      .udp
-      .local_addr()
-      .context("Failed reading local address")?;
-    let udp = self
-      .udp
-      .try_clone()
-      .map_err(|err| AppError::UdpSocketError { err })?;
-    udp
-      .set_write_timeout(Some(consts::UDP_WRITE_TIMEOUT))
-      .map_err(|err| AppError::UdpSocketError { err })?;
-    let server_udp_addr = self.server_udp_addr.clone();
-    let shutdown = Arc::clone(&self.shutdown);
-
-    Ok(thread::spawn(move || {
-      while !shutdown.load(Ordering::Acquire) {
-        let message = local_addr.to_string();
-
-        udp
-          .send_to(&message.as_bytes(), server_udp_addr)
-          .context(format!("Failed sending to UDP {server_udp_addr:?}"))?;
+  Ok(())
+}
 
-        thread::sleep(consts::HEALTH_CHECK_STREAMING_TIMEOUT);
+/// New quotes of recorded datagrams, paced by their receive times in `realtime`
+/// mode, latency is measured at the recorded receive times
+fn replay_quotes(
+  recording: &Path,
+  realtime: bool,
+  latency: LatencyStats,
+) -> Result<impl Iterator<Item = Result<StockQuote, AppError>>, AppError> {
+  let replay = Replay::open(recording)?;
+  let started = Instant::now();
+  let mut first_received_at_us = None;
+  let mut known = KnownQuotes::default();
+
+  Ok(replay.flat_map(move |record| {
+    let new_quotes = record.and_then(|record| {
+      if realtime {
+        let first = *first_received_at_us.get_or_insert(record.received_at_us);
+        let offset =
+          Duration::from_micros(record.received_at_us.saturating_sub(first));
+        thread::sleep(
+          (started + offset).saturating_duration_since(Instant::now()),
+        );
       }
 
-      info!("Stop healthcheck streaming");
+      let datagram = record.datagram()?;
+      let new_quotes = known.new_quotes(&datagram);
+      latency.record(&new_quotes, datagram.sent_at(), record.received_at_us);
 
-      Ok(())
-    }))
-  }
+      Ok(new_quotes)
+    });
+
+    match new_quotes {
+      Ok(new_quotes) => new_quotes.into_iter().map(Ok).collect(),
+      Err(e) => vec![Err(e)],
+    }
+  }))
 }
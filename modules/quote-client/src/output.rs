@@ -0,0 +1,204 @@
+use std::{
+  fmt,
+  fs::File,
+  io::{self, BufWriter, Write},
+  path::PathBuf,
+  str::FromStr,
+};
+
+use anyhow::Context;
+use common::{error::AppError, stock::StockQuote};
+
+const CSV_HEADER: &str = "ticker,price,volume,timestamp";
+
+/// Format of written quotes
+#[derive(Debug, Clone, Copy, Default, PartialEq, Eq)]
+pub enum OutputFormat {
+  /// One `JSON` object per line, missing fields are omitted
+  #[default]
+  JsonLines,
+  /// Comma separated values with a header, missing fields are empty
+  Csv,
+  /// Aligned columns with a header for humans
+  Table,
+}
+
+impl FromStr for OutputFormat {
+  type Err = anyhow::Error;
+
+  fn from_str(str: &str) -> Result<Self, Self::Err> {
+    match str.trim().to_lowercase().as_str() {
+      "json-lines" | "jsonl" => Ok(Self::JsonLines),
+      "csv" => Ok(Self::Csv),
+      "table" => Ok(Self::Table),
+      _ => anyhow::bail!("Unknown output format `{str}`"),
+    }
+  }
+}
+
+impl fmt::Display for OutputFormat {
+  fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+    let format = match self {
+      Self::JsonLines => "json-lines",
+      Self::Csv => "csv",
+      Self::Table => "table",
+    };
+
+    f.write_str(format)
+  }
+}
+
+/// Destination of written quotes, `-` stands for stdout
+#[derive(Debug, Clone, Default, PartialEq, Eq)]
+pub enum OutputTarget {
+  #[default]
+  Stdout,
+  File(PathBuf),
+}
+
+impl FromStr for OutputTarget {
+  type Err = anyhow::Error;
+
+  fn from_str(str: &str) -> Result<Self, Self::Err> {
+    match str.trim() {
+      "" => anyhow::bail!("Output path should not be empty"),
+      "-" => Ok(Self::Stdout),
+      path => Ok(Self::File(PathBuf::from(path))),
+    }
+  }
+}
+
+impl fmt::Display for OutputTarget {
+  fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+    match self {
+      Self::Stdout => f.write_str("-"),
+      Self::File(path) => write!(f, "{}", path.display()),
+    }
+  }
+}
+
+/// # Quote writer
+///
+/// Writes quotes in the output format, one quote per line. Each quote is
+/// flushed, so the feed may be piped into other tools as it arrives.
+///
+/// ```
+/// use common::stock::StockQuote;
+/// use quote_client::output::{OutputFormat, QuoteWriter};
+///
+/// let mut buf = Vec::new();
+/// let mut writer = QuoteWriter::new(OutputFormat::Csv, &mut buf);
+/// writer
+///   .write(&StockQuote {
+///     ticker: "AAPL".to_string(),
+///     price: Some(187.5),
+///     volume: None,
+///     timestamp: Some(1700000000000),
+///   })
+///   .unwrap();
+/// drop(writer);
+///
+/// assert_eq!(
+///   String::from_utf8(buf).unwrap(),
+///   "ticker,price,volume,timestamp\nAAPL,187.5,,1700000000000\n"
+/// );
+/// ```
+pub struct QuoteWriter<W: Write> {
+  format: OutputFormat,
+  writer: W,
+  header_written: bool,
+}
+
+impl QuoteWriter<Box<dyn Write + Send>> {
+  /// Writer to stdout or a created file
+  pub fn open(
+    target: &OutputTarget,
+    format: OutputFormat,
+  ) -> Result<Self, AppError> {
+    Ok(Self::new(format, open_target(target)?))
+  }
+}
+
+impl<W: Write> QuoteWriter<W> {
+  pub fn new(format: OutputFormat, writer: W) -> Self {
+    Self {
+      format,
+      writer,
+      header_written: false,
+    }
+  }
+  pub fn write(&mut self, quote: &StockQuote) -> Result<(), AppError> {
+    if !self.header_written {
+      self.header_written = true;
+      match self.format {
+        OutputFormat::JsonLines => {}
+        OutputFormat::Csv => writeln!(self.writer, "{CSV_HEADER}")?,
+        OutputFormat::Table => writeln!(
+          self.writer,
+          "{:<10} {:>14} {:>10} {:>15}",
+          "TICKER", "PRICE", "VOLUME", "TIMESTAMP"
+        )?,
+      }
+    }
+
+    match self.format {
+      OutputFormat::JsonLines => {
+        serde_json::to_writer(&mut self.writer, quote)
+          .context("Failed serializing quote")?;
+        writeln!(self.writer)?;
+      }
+      OutputFormat::Csv => writeln!(
+        self.writer,
+        "{},{},{},{}",
+        csv_field(&quote.ticker),
+        optional(quote.price),
+        optional(quote.volume),
+        optional(quote.timestamp),
+      )?,
+      OutputFormat::Table => writeln!(
+        self.writer,
+        "{:<10} {:>14} {:>10} {:>15}",
+        quote.ticker,
+        quote
+          .price
+          .map_or_else(|| "-".to_string(), |price| format!("{price:.4}")),
+        quote
+          .volume
+          .map_or_else(|| "-".to_string(), |volume| volume.to_string()),
+        quote
+          .timestamp
+          .map_or_else(|| "-".to_string(), |timestamp| timestamp.to_string()),
+      )?,
+    }
+
+    self.writer.flush()?;
+
+    Ok(())
+  }
+}
+
+/// Stdout or a created file
+pub(crate) fn open_target(
+  target: &OutputTarget,
+) -> Result<Box<dyn Write + Send>, AppError> {
+  Ok(match target {
+    OutputTarget::Stdout => Box::new(io::stdout()),
+    OutputTarget::File(path) => Box::new(BufWriter::new(
+      File::create(path)
+        .context(format!("Failed creating output file {path:?}"))?,
+    )),
+  })
+}
+
+pub(crate) fn optional(value: Option<impl ToString>) -> String {
+  value.map(|value| value.to_string()).unwrap_or_default()
+}
+
+// Quote fields containing separators, as in RFC 4180
+pub(crate) fn csv_field(value: &str) -> String {
+  if value.contains([',', '"', '\n', '\r']) {
+    format!("\"{}\"", value.replace('"', "\"\""))
+  } else {
+    value.to_string()
+  }
+}
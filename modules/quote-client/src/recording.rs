@@ -0,0 +1,324 @@
+//! # Feed recording
+//!
+//! Recording is a `JSON` lines file, which is only appended to. The first
+//! line is a header, each following line holds one datagram received from
+//! server:
+//!
+//! ```text
+//! {"format":"quote-client-recording","version":1}
+//! {"received_at_us":1700000000123456,"subscription":1,"seq":7,"datagram":{"type":"snapshot",...}}
+//! {"received_at_us":1700000000173456,"subscription":1,"seq":8,"datagram":{...},"sealed":"01a4..."}
+//! ```
+//!
+//! - `received_at_us` Receive time in microseconds since Unix epoch
+//! - `subscription` Subscription number, sequence numbers restart with each
+//!   resubscription
+//! - `seq` Datagram sequence number, missing for challenges
+//! - `datagram` Datagram payload as received, or as opened from the sealed
+//!   datagram
+//! - `sealed` Hex encoded sealed datagram as received, with `--encrypt` only
+//!
+//! Each line is appended with a single write, so a crash may leave only the
+//! last line incomplete. Readers skip such truncated tail, and recorder drops
+//! it before appending to an existing recording.
+
+use std::{
+  fs::{File, OpenOptions},
+  io::{BufRead, BufReader, Read, Seek, SeekFrom, Write},
+  path::{Path, PathBuf},
+  time::{SystemTime, UNIX_EPOCH},
+};
+
+use anyhow::Context;
+use serde_json::value::RawValue;
+use tracing::warn;
+
+use common::{error::AppError, stock::StockDatagram};
+
+const FORMAT: &str = "quote-client-recording";
+const VERSION: u32 = 1;
+
+#[derive(Debug, serde::Serialize, serde::Deserialize)]
+struct Header {
+  format: String,
+  version: u32,
+}
+
+/// Datagram received from server
+#[derive(Debug, serde::Deserialize)]
+pub struct Record {
+  /// Receive time in microseconds since Unix epoch
+  pub received_at_us: u64,
+  /// Subscription number, sequence numbers restart with each resubscription
+  pub subscription: u64,
+  pub seq: Option<u64>,
+  /// Datagram payload as received, or as opened from the sealed datagram
+  pub datagram: Box<RawValue>,
+  /// Hex encoded sealed datagram as received
+  pub sealed: Option<String>,
+}
+
+impl Record {
+  pub fn datagram(&self) -> Result<StockDatagram, AppError> {
+    serde_json::from_str(self.datagram.get())
+      .map_err(|err| AppError::DeserializationError { err })
+  }
+}
+
+#[derive(Debug, serde::Serialize)]
+struct RecordRef<'a> {
+  received_at_us: u64,
+  subscription: u64,
+  #[serde(skip_serializing_if = "Option::is_none")]
+  seq: Option<u64>,
+  datagram: &'a RawValue,
+  #[serde(skip_serializing_if = "Option::is_none")]
+  sealed: Option<String>,
+}
+
+/// # Recorder
+///
+/// Appends received datagrams to a recording, which is created when missing.
+///
+/// ```
+/// use std::time::SystemTime;
+///
+/// use quote_client::recording::{Recorder, Replay};
+///
+/// let path = std::env::temp_dir().join("quote-client-recording-doctest.jsonl");
+/// # let _ = std::fs::remove_file(&path);
+/// let mut recorder = Recorder::open(&path).unwrap();
+/// recorder
+///   .record(
+///     SystemTime::now(),
+///     1,
+///     br#"{"type":"heartbeat","seq":1}"#,
+///     None,
+///   )
+///   .unwrap();
+///
+/// let mut replay = Replay::open(&path).unwrap();
+/// let record = replay.next().unwrap().unwrap();
+///
+/// assert_eq!(record.seq, Some(1));
+/// assert!(replay.next().is_none());
+/// # std::fs::remove_file(&path).unwrap();
+/// ```
+#[derive(Debug)]
+pub struct Recorder {
+  file: File,
+}
+
+impl Recorder {
+  /// Open recording for appending, the truncated tail of a previous crash is
+  /// dropped. Files which aren't recordings are refused and left intact.
+  pub fn open(path: &Path) -> Result<Self, AppError> {
+    let mut file = OpenOptions::new()
+      .read(true)
+      .append(true)
+      .create(true)
+      .open(path)
+      .context(format!("Failed opening recording {path:?}"))?;
+
+    let mut header = serde_json::to_vec(&Header {
+      format: FORMAT.to_string(),
+      version: VERSION,
+    })
+    .context("Failed serializing recording header")?;
+    header.push(b'\n');
+
+    let len = file.metadata()?.len();
+    let complete = if len == 0 {
+      0
+    } else {
+      file.seek(SeekFrom::Start(0))?;
+      let has_header = read_header(&mut BufReader::new(&mut file))?;
+
+      if has_header {
+        complete_len(&mut file)?
+      } else if is_header_part(&mut file, len, &header)? {
+        // Crash while writing the header
+        0
+      } else {
+        return Err(AppError::InvalidRecording {
+          line: 1,
+          reason: "Missing recording header".to_string(),
+        });
+      }
+    };
+
+    if complete < len {
+      warn!(path = ?path, "Dropped truncated recording tail:");
+      file.set_len(complete)?;
+    }
+    if complete == 0 {
+      file.write_all(&header)?;
+    }
+
+    Ok(Self { file })
+  }
+  /// Recorder appending to the same recording
+  pub fn try_clone(&self) -> Result<Self, AppError> {
+    Ok(Self {
+      file: self.file.try_clone()?,
+    })
+  }
+  /// Append received `payload`, the opened payload of `sealed` datagrams
+  pub fn record(
+    &mut self,
+    received_at: SystemTime,
+    subscription: u64,
+    payload: &[u8],
+    sealed: Option<&[u8]>,
+  ) -> Result<(), AppError> {
+    let datagram = serde_json::from_slice::<&RawValue>(payload)
+      .map_err(|err| AppError::DeserializationError { err })?;
+    let seq = serde_json::from_str::<StockDatagram>(datagram.get())
+      .ok()
+      .and_then(|datagram| datagram.seq());
+    let received_at_us = received_at
+      .duration_since(UNIX_EPOCH)
+      .unwrap_or_default()
+      .as_micros() as u64;
+
+    let mut line = serde_json::to_vec(&RecordRef {
+      received_at_us,
+      subscription,
+      seq,
+      datagram,
+      sealed: sealed.map(hex::encode),
+    })
+    .context("Failed serializing record")?;
+    line.push(b'\n');
+
+    self.file.write_all(&line)?;
+
+    Ok(())
+  }
+}
+
+/// # Replay
+///
+/// Iterator over records of a recording. Iteration ends at the truncated
+/// tail, which is reported by `is_truncated`.
+#[derive(Debug)]
+pub struct Replay {
+  reader: BufReader<File>,
+  path: PathBuf,
+  line: usize,
+  truncated: bool,
+}
+
+impl Replay {
+  pub fn open(path: &Path) -> Result<Self, AppError> {
+    let file =
+      File::open(path).context(format!("Failed opening recording {path:?}"))?;
+    let mut reader = BufReader::new(file);
+    let truncated = !read_header(&mut reader)?;
+
+    Ok(Self {
+      reader,
+      path: path.to_path_buf(),
+      line: 1,
+      truncated,
+    })
+  }
+  /// Recording ends with an incomplete line
+  pub fn is_truncated(&self) -> bool {
+    self.truncated
+  }
+}
+
+impl Iterator for Replay {
+  type Item = Result<Record, AppError>;
+
+  fn next(&mut self) -> Option<Self::Item> {
+    if self.truncated {
+      return None;
+    }
+
+    let mut line = Vec::new();
+    match self.reader.read_until(b'\n', &mut line) {
+      Ok(0) => return None,
+      Ok(_) => self.line += 1,
+      Err(e) => return Some(Err(e.into())),
+    }
+
+    if line.last() != Some(&b'\n') {
+      warn!(path = ?self.path, line = self.line, "Truncated recording tail:");
+      self.truncated = true;
+      return None;
+    }
+
+    Some(serde_json::from_slice::<Record>(&line).map_err(|err| {
+      AppError::InvalidRecording {
+        line: self.line,
+        reason: err.to_string(),
+      }
+    }))
+  }
+}
+
+/// Check recording header, `false` when it's truncated
+fn read_header(reader: &mut impl BufRead) -> Result<bool, AppError> {
+  let mut line = Vec::new();
+  reader.read_until(b'\n', &mut line)?;
+
+  if line.last() != Some(&b'\n') {
+    return Ok(false);
+  }
+
+  match serde_json::from_slice::<Header>(&line) {
+    Ok(Header { format, version }) if format == FORMAT => {
+      if version != VERSION {
+        return Err(AppError::InvalidRecording {
+          line: 1,
+          reason: format!("Unsupported version {version}"),
+        });
+      }
+
+      Ok(true)
+    }
+    _ => Err(AppError::InvalidRecording {
+      line: 1,
+      reason: "Missing recording header".to_string(),
+    }),
+  }
+}
+
+/// File of `len` bytes holds the beginning of `header` only
+fn is_header_part(
+  file: &mut File,
+  len: u64,
+  header: &[u8],
+) -> Result<bool, AppError> {
+  if len >= header.len() as u64 {
+    return Ok(false);
+  }
+
+  let mut content = Vec::new();
+  file.seek(SeekFrom::Start(0))?;
+  file.read_to_end(&mut content)?;
+
+  Ok(header.starts_with(&content))
+}
+
+/// Length of the recording up to the last complete line
+fn complete_len(file: &mut File) -> Result<u64, AppError> {
+  let mut len = file.metadata()?.len();
+  let mut buf = [0u8; 4096];
+
+  while len > 0 {
+    let chunk = len.min(buf.len() as u64);
+    file.seek(SeekFrom::Start(len - chunk))?;
+    let chunk_buf = &mut buf[..chunk as usize];
+    file.read_exact(chunk_buf)?;
+
+    if let Some(newline) = chunk_buf.iter().rposition(|byte| *byte == b'\n') {
+      return Ok(len - chunk + newline as u64 + 1);
+    }
+    len -= chunk;
+  }
+
+  Ok(0)
+}
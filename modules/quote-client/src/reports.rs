@@ -0,0 +1,84 @@
+use std::{
+  sync::{
+    Arc,
+    atomic::{AtomicBool, Ordering},
+  },
+  thread::{self, JoinHandle},
+  time::{Duration, Instant},
+};
+
+use tracing::info;
+
+use quote_client::{
+  consts,
+  latency::{LatencyStats, LatencySummary},
+};
+
+/// Logs latency of received quotes on interval, and once more when finished
+pub(crate) struct LatencyReporter {
+  latency: LatencyStats,
+  reporter: JoinHandle<()>,
+}
+
+impl LatencyReporter {
+  /// Report until `shutdown` is set
+  pub(crate) fn spawn(
+    latency: LatencyStats,
+    interval: Duration,
+    shutdown: Arc<AtomicBool>,
+  ) -> Self {
+    let stats = latency.clone();
+    let reporter = thread::spawn(move || {
+      let mut reported_at = Instant::now();
+
+      while !shutdown.load(Ordering::Acquire) {
+        thread::sleep(consts::SHUTDOWN_POLL_INTERVAL.min(interval));
+
+        if reported_at.elapsed() >= interval {
+          reported_at = Instant::now();
+          log_latency(&stats);
+        }
+      }
+    });
+
+    Self { latency, reporter }
+  }
+  pub(crate) fn finish(self) {
+    let _ = self.reporter.join();
+
+    info!("Final latency report");
+    log_latency(&self.latency);
+  }
+}
+
+/// Latency percentiles of every ticker, `*` stands for all tickers
+pub(crate) fn log_latency(latency: &LatencyStats) {
+  for summary in latency.summary() {
+    let LatencySummary {
+      ticker,
+      count,
+      p50,
+      p99,
+      max,
+      network_p50,
+      network_p99,
+      network_max,
+    } = summary;
+    let ticker = ticker.as_deref().unwrap_or("*");
+
+    match (network_p50, network_p99, network_max) {
+      (Some(network_p50), Some(network_p99), Some(network_max)) => info!(
+        ticker,
+        count,
+        p50 = ?p50,
+        p99 = ?p99,
+        max = ?max,
+        network_p50 = ?network_p50,
+        network_p99 = ?network_p99,
+        network_max = ?network_max,
+        "Latency:"
+      ),
+      _ => info!(ticker, count, p50 = ?p50, p99 = ?p99, max = ?max, "Latency:"),
+    }
+  }
+}
@@ -0,0 +1,251 @@
+use std::{
+  collections::HashMap,
+  net::SocketAddr,
+  path::PathBuf,
+  sync::{
+    Arc,
+    atomic::{AtomicBool, Ordering},
+    mpsc::{self, Receiver},
+  },
+  thread::{self, JoinHandle},
+  time::Duration,
+};
+
+use anyhow::anyhow;
+use tracing::info;
+
+use common::{
+  error::AppError,
+  stock::{Credentials, StockDatagram, StockQuote, SubscriptionOptions},
+};
+
+use crate::{
+  client::{Client, ClientTls},
+  configs::consts,
+  connection::{FeedStatus, StreamState},
+  latency::LatencyStats,
+};
+
+/// Server addresses and parameters of a subscription
+#[derive(Debug, Clone)]
+pub struct SubscriptionConfig {
+  pub server_tcp_addr: SocketAddr,
+  /// UDP port of server, which shares IP with TCP address
+  pub server_udp_port: u16,
+  /// Address quotes are received at, port `0` binds an ephemeral port
+  pub client_udp_addr: SocketAddr,
+  /// Tickers, patterns or group names
+  pub tickers: Vec<String>,
+  pub options: SubscriptionOptions,
+  pub auth: Option<Credentials>,
+  /// TLS settings of control channel, plain TCP when not set
+  pub tls: Option<ClientTls>,
+  /// Time without data or server heartbeats before feed is stale
+  pub stale_after: Duration,
+  /// Recording received datagrams are appended to, see
+  /// [`recording`](crate::recording)
+  pub record: Option<PathBuf>,
+}
+
+impl SubscriptionConfig {
+  /// Plain TCP subscription with default options
+  pub fn new(
+    server_tcp_addr: SocketAddr,
+    server_udp_port: u16,
+    client_udp_addr: SocketAddr,
+    tickers: Vec<String>,
+  ) -> Self {
+    Self {
+      server_tcp_addr,
+      server_udp_port,
+      client_udp_addr,
+      tickers,
+      options: SubscriptionOptions::default(),
+      auth: None,
+      tls: None,
+      stale_after: consts::STALE_FEED_TIMEOUT,
+      record: None,
+    }
+  }
+}
+
+/// # Subscription
+///
+/// Subscribes to server and keeps the subscription alive in background
+/// threads: heartbeats are sent on the negotiated interval, and lost streams
+/// are resubscribed with backoff. Received quotes are buffered until read,
+/// quotes received when the buffer is full are dropped.
+///
+/// Quotes iterator ends once the subscription is closed by shutdown or
+/// rejected by server, `join` reports the reason.
+///
+/// ```no_run
+/// use std::sync::{Arc, atomic::AtomicBool};
+///
+/// use common::error::AppError;
+/// use quote_client::{Subscription, SubscriptionConfig};
+///
+/// fn main() -> Result<(), AppError> {
+///   let config = SubscriptionConfig::new(
+///     "127.0.0.1:8000".parse().unwrap(),
+///     8001,
+///     "127.0.0.1:0".parse().unwrap(),
+///     vec!["AAPL".to_string(), "TECH".to_string()],
+///   );
+///   let subscription =
+///     Subscription::start(config, Arc::new(AtomicBool::new(false)))?;
+///
+///   for quote in subscription.quotes().take(10) {
+///     println!("{} {:?}", quote.ticker, quote.price);
+///   }
+///
+///   subscription.shutdown();
+///   subscription.join()
+/// }
+/// ```
+#[derive(Debug)]
+pub struct Subscription {
+  quotes: Receiver<StockQuote>,
+  stream: Arc<StreamState>,
+  local_addr: SocketAddr,
+  shutdown: Arc<AtomicBool>,
+  control: Option<JoinHandle<Result<(), AppError>>>,
+}
+
+impl Subscription {
+  /// Bind client UDP address and subscribe in background, subscription stops
+  /// once `shutdown` is set
+  pub fn start(
+    config: SubscriptionConfig,
+    shutdown: Arc<AtomicBool>,
+  ) -> Result<Self, AppError> {
+    let (sender, quotes) = mpsc::sync_channel(consts::QUOTES_BUFFER_CAPACITY);
+    let server_tcp_addr = config.server_tcp_addr;
+    let server_udp_port = config.server_udp_port;
+    let client = Client::new(config, sender, Arc::clone(&shutdown))?;
+    let local_addr = client.local_addr()?;
+    let stream = client.stream();
+
+    info!(
+      server = %server_tcp_addr,
+      server_udp = %server_udp_port,
+      client_udp = %local_addr,
+      "Initialized client"
+    );
+
+    let control = thread::spawn(move || client.run());
+
+    Ok(Self {
+      quotes,
+      stream,
+      local_addr,
+      shutdown,
+      control: Some(control),
+    })
+  }
+  /// Blocking iterator over received quotes
+  pub fn quotes(&self) -> mpsc::Iter<'_, StockQuote> {
+    self.quotes.iter()
+  }
+  /// Received quote if any, without blocking
+  pub fn try_quote(&self) -> Option<StockQuote> {
+    self.quotes.try_recv().ok()
+  }
+  /// Quote received within `timeout`, `None` on timeout or once closed
+  pub fn recv_timeout(&self, timeout: Duration) -> Option<StockQuote> {
+    self.quotes.recv_timeout(timeout).ok()
+  }
+  /// Connection state and datagram counters
+  pub fn status(&self) -> FeedStatus {
+    self.stream.status()
+  }
+  /// Latency of received quotes, shared with the receiving thread
+  pub fn latency(&self) -> LatencyStats {
+    self.stream.latency()
+  }
+  /// Bound client UDP address
+  pub fn local_addr(&self) -> SocketAddr {
+    self.local_addr
+  }
+  pub fn is_closed(&self) -> bool {
+    self.control.as_ref().is_none_or(JoinHandle::is_finished)
+  }
+  /// Stop subscription, quotes iterator ends after buffered quotes
+  pub fn shutdown(&self) {
+    self.shutdown.store(true, Ordering::Release);
+  }
+  /// Wait for subscription to close
+  pub fn join(mut self) -> Result<(), AppError> {
+    match self.control.take() {
+      Some(control) => control.join().map_err(|_| {
+        AppError::OtherError(anyhow!("Failed waiting for client thread"))
+      })?,
+      None => Ok(()),
+    }
+  }
+}
+
+impl Drop for Subscription {
+  fn drop(&mut self) {
+    if self.control.is_some() {
+      self.shutdown();
+    }
+  }
+}
+
+/// # Known quotes
+///
+/// Quotes last received per ticker. Delta mode refresh snapshots resend
+/// quotes the client already has, so only quotes which differ from the known
+/// ones are new.
+///
+/// ```
+/// use common::stock::{StockDatagram, StockQuote};
+/// use quote_client::KnownQuotes;
+///
+/// let quote = StockQuote {
+///   ticker: "AAPL".to_string(),
+///   price: Some(1.5),
+///   volume: Some(100),
+///   timestamp: Some(0),
+/// };
+/// let mut known = KnownQuotes::default();
+///
+/// let delta = StockDatagram::Delta {
+///   seq: 1,
+///   sent_at: None,
+///   quotes: vec![quote.clone()],
+/// };
+/// assert_eq!(known.new_quotes(&delta).len(), 1);
+///
+/// let refresh = StockDatagram::Snapshot {
+///   seq: 2,
+///   sent_at: None,
+///   refresh: true,
+///   quotes: vec![quote],
+/// };
+/// assert!(known.new_quotes(&refresh).is_empty());
+/// ```
+#[derive(Debug, Default)]
+pub struct KnownQuotes {
+  quotes: HashMap<String, StockQuote>,
+}
+
+impl KnownQuotes {
+  /// Quotes of `datagram` new to the client
+  pub fn new_quotes(&mut self, datagram: &StockDatagram) -> Vec<StockQuote> {
+    let refresh =
+      matches!(datagram, StockDatagram::Snapshot { refresh: true, .. });
+    let mut new_quotes = Vec::new();
+
+    for quote in datagram.quotes() {
+      let known = self.quotes.insert(quote.ticker.clone(), quote.clone());
+
+      if !refresh || known.as_ref() != Some(quote) {
+        new_quotes.push(quote.clone());
+      }
+    }
+
+    new_quotes
+  }
+}
@@ -0,0 +1,249 @@
+use std::{
+  collections::BTreeMap,
+  sync::atomic::{AtomicBool, Ordering},
+  time::{Duration, Instant},
+};
+
+use ratatui::{
+  DefaultTerminal, Frame,
+  crossterm::event::{self, Event, KeyCode, KeyEventKind, KeyModifiers},
+  layout::{Constraint, Layout},
+  style::{Color, Modifier, Style, Stylize},
+  text::{Line, Span},
+  widgets::{Block, Cell, Paragraph, Row, Table},
+};
+
+use common::{error::AppError, stock::StockQuote};
+use quote_client::{
+  ConnectionState, FeedStatus, Subscription, latency::LatencySummary,
+};
+
+use crate::consumers::Consumers;
+
+const RENDER_INTERVAL: Duration = Duration::from_millis(200);
+const RATE_WINDOW: Duration = Duration::from_secs(1);
+
+/// Direction of the latest price change
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+enum Tick {
+  Up,
+  Down,
+  Flat,
+}
+
+#[derive(Debug)]
+struct TickerRow {
+  first_price: Option<f64>,
+  last_price: Option<f64>,
+  volume: Option<u32>,
+  tick: Tick,
+  updated_at: Instant,
+}
+
+/// Latest quotes per ticker and feed rate, rendered on each frame
+#[derive(Debug)]
+struct Dashboard {
+  rows: BTreeMap<String, TickerRow>,
+  status: FeedStatus,
+  /// Latency of every ticker
+  latency: Option<LatencySummary>,
+  rate: f64,
+  rate_sample: (Instant, u64),
+}
+
+impl Dashboard {
+  fn new(status: FeedStatus) -> Self {
+    Self {
+      rows: BTreeMap::new(),
+      status,
+      latency: None,
+      rate: 0.0,
+      rate_sample: (Instant::now(), status.datagrams),
+    }
+  }
+  fn apply(&mut self, quote: StockQuote, now: Instant) {
+    let row = self.rows.entry(quote.ticker).or_insert(TickerRow {
+      first_price: quote.price,
+      last_price: None,
+      volume: None,
+      tick: Tick::Flat,
+      updated_at: now,
+    });
+
+    if let Some(price) = quote.price {
+      row.first_price.get_or_insert(price);
+      row.tick = match row.last_price {
+        Some(last) if price > last => Tick::Up,
+        Some(last) if price < last => Tick::Down,
+        _ => Tick::Flat,
+      };
+      row.last_price = Some(price);
+    }
+    if quote.volume.is_some() {
+      row.volume = quote.volume;
+    }
+    row.updated_at = now;
+  }
+  fn update_status(&mut self, status: FeedStatus, now: Instant) {
+    let (sampled_at, datagrams) = self.rate_sample;
+    let elapsed = now.duration_since(sampled_at);
+
+    if elapsed >= RATE_WINDOW {
+      self.rate = status.datagrams.saturating_sub(datagrams) as f64
+        / elapsed.as_secs_f64();
+      self.rate_sample = (now, status.datagrams);
+    }
+    self.status = status;
+  }
+}
+
+/// Live table of subscribed tickers, until `q`, `Esc`, `Ctrl-C`, shutdown
+/// or subscription close
+pub(crate) fn run(
+  subscription: &Subscription,
+  shutdown: &AtomicBool,
+  consumers: &mut Consumers,
+) -> Result<(), AppError> {
+  let mut terminal = ratatui::try_init()?;
+  let result = render_loop(&mut terminal, subscription, shutdown, consumers);
+  ratatui::try_restore()?;
+
+  result
+}
+
+fn render_loop(
+  terminal: &mut DefaultTerminal,
+  subscription: &Subscription,
+  shutdown: &AtomicBool,
+  consumers: &mut Consumers,
+) -> Result<(), AppError> {
+  let mut dashboard = Dashboard::new(subscription.status());
+
+  while !shutdown.load(Ordering::Acquire) && !subscription.is_closed() {
+    let now = Instant::now();
+    while let Some(quote) = subscription.try_quote() {
+      consumers.consume(&quote)?;
+      dashboard.apply(quote, now);
+    }
+    dashboard.update_status(subscription.status(), now);
+    dashboard.latency = subscription.latency().summary().into_iter().next();
+
+    terminal.draw(|frame| draw(frame, &dashboard, now))?;
+
+    if event::poll(RENDER_INTERVAL)? {
+      if let Event::Key(key) = event::read()? {
+        let quit = key.kind == KeyEventKind::Press
+          && (matches!(key.code, KeyCode::Char('q') | KeyCode::Esc)
+            || (key.code == KeyCode::Char('c')
+              && key.modifiers.contains(KeyModifiers::CONTROL)));
+
+        if quit {
+          shutdown.store(true, Ordering::Release);
+        }
+      }
+    }
+  }
+
+  Ok(())
+}
+
+fn draw(frame: &mut Frame, dashboard: &Dashboard, now: Instant) {
+  let [header, table] =
+    Layout::vertical([Constraint::Length(3), Constraint::Min(0)])
+      .areas(frame.area());
+
+  let FeedStatus {
+    state,
+    datagrams,
+    lost,
+    silence,
+  } = dashboard.status;
+  let state_color = match state {
+    ConnectionState::Subscribed => Color::Green,
+    ConnectionState::Stale | ConnectionState::Connecting => Color::Yellow,
+    _ => Color::Red,
+  };
+  let status =
+    Line::from(vec![
+      Span::raw(" Feed: "),
+      Span::styled(state.to_string(), Style::new().fg(state_color).bold()),
+      Span::raw(format!(
+        "   Messages/s: {:.1}   Messages: {datagrams}   Gaps: {lost}   \
+       Last message: {:.1}s ago   Tickers: {}",
+        dashboard.rate,
+        silence.as_secs_f64(),
+        dashboard.rows.len()
+      )),
+      Span::raw(dashboard.latency.as_ref().map_or_else(
+        String::new,
+        |latency| {
+          format!(
+            "   Latency p50/p99: {:.1}/{:.1}ms",
+            latency.p50.as_secs_f64() * 1000.0,
+            latency.p99.as_secs_f64() * 1000.0
+          )
+        },
+      )),
+    ]);
+  frame.render_widget(
+    Paragraph::new(status)
+      .block(Block::bordered().title(" quote-client · q to quit ")),
+    header,
+  );
+
+  let rows = dashboard.rows.iter().map(|(ticker, row)| {
+    let change = row
+      .last_price
+      .zip(row.first_price)
+      .map(|(last, first)| (last - first, (last - first) / first * 100.0));
+    let color = match row.tick {
+      Tick::Up => Color::Green,
+      Tick::Down => Color::Red,
+      Tick::Flat => Color::Reset,
+    };
+
+    Row::new([
+      Cell::from(ticker.as_str()),
+      Cell::from(format_optional(row.last_price, |price| {
+        format!("{price:.4}")
+      })),
+      Cell::from(format_optional(change, |(change, _)| {
+        format!("{change:+.4}")
+      })),
+      Cell::from(format_optional(change, |(_, percent)| {
+        format!("{percent:+.2}%")
+      })),
+      Cell::from(format_optional(row.volume, |volume| volume.to_string())),
+      Cell::from(format!(
+        "{:.1}s",
+        now.duration_since(row.updated_at).as_secs_f64()
+      )),
+    ])
+    .style(Style::new().fg(color))
+  });
+  let widths = [
+    Constraint::Length(10),
+    Constraint::Length(14),
+    Constraint::Length(12),
+    Constraint::Length(10),
+    Constraint::Length(10),
+    Constraint::Length(8),
+  ];
+  let table_header =
+    Row::new(["Ticker", "Last", "Change", "% Change", "Volume", "Age"])
+      .style(Style::new().add_modifier(Modifier::BOLD));
+
+  frame.render_widget(
+    Table::new(rows, widths)
+      .header(table_header)
+      .block(Block::bordered().title(" Subscribed tickers ")),
+    table,
+  );
+}
+
+fn format_optional<T>(
+  value: Option<T>,
+  format: impl Fn(T) -> String,
+) -> String {
+  value.map(format).unwrap_or_else(|| "-".to_string())
+}
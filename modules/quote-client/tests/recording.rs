@@ -0,0 +1,91 @@
+use std::{env, fs, io::Write, path::PathBuf, process};
+
+use common::{error::AppError, stock::StockQuote};
+use quote_client::{
+  SubscriptionConfig,
+  recording::{Recorder, Replay},
+};
+use test_support::{RECV_TIMEOUT, start, subscription_config, test_server};
+
+/// Fresh recording path of the test `name`
+fn recording_path(name: &str) -> PathBuf {
+  let path = env::temp_dir()
+    .join(format!("quote-recording-{name}-{}.jsonl", process::id()));
+  let _ = fs::remove_file(&path);
+
+  path
+}
+
+#[test]
+fn received_datagrams_are_recorded() {
+  let recording = recording_path("received");
+  let server = test_server().spawn().unwrap();
+  let subscription = start(SubscriptionConfig {
+    record: Some(recording.clone()),
+    ..subscription_config(&server, &["AAPL", "MSFT"])
+  });
+
+  let received: Vec<StockQuote> = (0..10)
+    .map(|_| subscription.recv_timeout(RECV_TIMEOUT).unwrap())
+    .collect();
+  subscription.shutdown();
+  subscription.join().unwrap();
+
+  let records: Vec<_> = Replay::open(&recording)
+    .unwrap()
+    .map(|record| record.unwrap())
+    .collect();
+  let replayed: Vec<StockQuote> = records
+    .iter()
+    .flat_map(|record| record.datagram().unwrap().quotes().to_vec())
+    .collect();
+  assert!(replayed.starts_with(&received));
+
+  // Crash while appending leaves an incomplete line
+  fs::OpenOptions::new()
+    .append(true)
+    .open(&recording)
+    .unwrap()
+    .write_all(br#"{"received_at_us":17000"#)
+    .unwrap();
+  let mut replay = Replay::open(&recording).unwrap();
+  assert_eq!(replay.by_ref().count(), records.len());
+  assert!(replay.is_truncated());
+
+  // Recorder drops the incomplete line before appending
+  drop(Recorder::open(&recording).unwrap());
+  let mut replay = Replay::open(&recording).unwrap();
+  assert!(replay.by_ref().all(|record| record.is_ok()));
+  assert!(!replay.is_truncated());
+
+  fs::remove_file(&recording).unwrap();
+}
+
+#[test]
+fn other_files_are_not_recorded_to() {
+  let path = recording_path("other");
+  let content = "AAPL\nMSFT\nincomplete";
+  fs::write(&path, content).unwrap();
+
+  assert!(matches!(
+    Recorder::open(&path),
+    Err(AppError::InvalidRecording { line: 1, .. })
+  ));
+  assert_eq!(fs::read_to_string(&path).unwrap(), content);
+
+  fs::remove_file(&path).unwrap();
+}
+
+#[test]
+fn truncated_header_is_rewritten() {
+  let path = recording_path("header");
+  fs::write(&path, r#"{"format":"quote-cl"#).unwrap();
+
+  drop(Recorder::open(&path).unwrap());
+  let mut replay = Replay::open(&path).unwrap();
+
+  assert!(replay.next().is_none());
+  assert!(!replay.is_truncated());
+
+  fs::remove_file(&path).unwrap();
+}
@@ -0,0 +1,131 @@
+use std::{
+  collections::HashMap,
+  fs::File,
+  io::{BufRead, BufReader},
+  num::NonZeroU32,
+  path::PathBuf,
+};
+
+use anyhow::Context;
+use ring::{digest, pbkdf2};
+
+use common::{error::AppError, stock::Credentials};
+
+use crate::limits::Limits;
+
+// Salt of the hash verified for unknown users
+const UNKNOWN_USER_SALT: &str = "unknown user";
+
+/// Subscription access rules
+#[derive(Debug, Default)]
+pub struct AccessPolicy {
+  /// Reject UDP addresses with IP other than the TCP peer IP
+  pub require_same_ip: bool,
+  /// Authentication is required when users are set
+  pub users: Option<UserStore>,
+  /// Ticker patterns each user is entitled to
+  pub entitlements: Option<HashMap<String, Vec<String>>>,
+  /// Connection and subscription limits
+  pub limits: Limits,
+}
+
+#[derive(Debug)]
+struct PasswordHash {
+  iterations: NonZeroU32,
+  salt: String,
+  hash: Vec<u8>,
+}
+
+/// Users allowed to subscribe, loaded from a local credentials file
+///
+/// Each line defines a user as `USERNAME:ITERATIONS:SALT:HASH`, where `HASH`
+/// is hex encoded PBKDF2-HMAC-SHA256 of password with salt and iterations,
+/// e.g. `python3 -c 'import hashlib, sys; print(hashlib.pbkdf2_hmac("sha256",
+/// sys.argv[2].encode(), sys.argv[1].encode(), 600000).hex())' SALT PASSWORD`.
+/// Unknown users are verified against a hash with the most iterations of
+/// the file, so response time doesn't tell which users exist.
+#[derive(Debug)]
+pub struct UserStore {
+  users: HashMap<String, PasswordHash>,
+  unknown_user: PasswordHash,
+}
+
+impl UserStore {
+  pub fn read(path: PathBuf) -> Result<Self, AppError> {
+    let file = File::open(path).context("Failed reading credentials file")?;
+    let reader = BufReader::new(file);
+    let mut users = HashMap::new();
+
+    for line in reader.lines() {
+      let line = line.context("Failed reading credentials file")?;
+      let line = line.trim();
+      if line.is_empty() || line.starts_with('#') {
+        continue;
+      }
+
+      let mut parts = line.splitn(4, ':').map(str::trim);
+      let (Some(username), Some(iterations), Some(salt), Some(hash)) =
+        (parts.next(), parts.next(), parts.next(), parts.next())
+      else {
+        return Err(AppError::InvalidCredentialsEntry {
+          username: line.split(':').next().unwrap_or_default().to_string(),
+        });
+      };
+      let (Ok(iterations), Ok(hash)) =
+        (iterations.parse::<NonZeroU32>(), hex::decode(hash))
+      else {
+        return Err(AppError::InvalidCredentialsEntry {
+          username: username.to_string(),
+        });
+      };
+
+      users.insert(
+        username.to_string(),
+        PasswordHash {
+          iterations,
+          salt: salt.to_string(),
+          hash,
+        },
+      );
+    }
+
+    let unknown_user = PasswordHash {
+      iterations: users
+        .values()
+        .map(|user| user.iterations)
+        .max()
+        .unwrap_or(NonZeroU32::MIN),
+      salt: UNKNOWN_USER_SALT.to_string(),
+      hash: vec![0; digest::SHA256_OUTPUT_LEN],
+    };
+
+    Ok(Self {
+      users,
+      unknown_user,
+    })
+  }
+  /// Authenticated username, `None` for unknown user or wrong password
+  pub fn authenticate<'a>(
+    &self,
+    credentials: &'a Credentials,
+  ) -> Option<&'a str> {
+    let user = self.users.get(&credentials.username);
+    let PasswordHash {
+      iterations,
+      salt,
+      hash,
+    } = user.unwrap_or(&self.unknown_user);
+
+    // Derived key is compared in constant time, unknown users never match
+    let verified = pbkdf2::verify(
+      pbkdf2::PBKDF2_HMAC_SHA256,
+      *iterations,
+      salt.as_bytes(),
+      credentials.password.as_bytes(),
+      hash,
+    );
+
+    (user.is_some() && verified.is_ok())
+      .then_some(credentials.username.as_str())
+  }
+}
@@ -0,0 +1,232 @@
+use std::{
+  collections::HashMap,
+  net::SocketAddr,
+  sync::{
+    Arc,
+    atomic::{AtomicBool, Ordering},
+  },
+  thread::{self, JoinHandle},
+  time::Duration,
+};
+
+use anyhow::anyhow;
+use tracing::info;
+
+use common::error::AppError;
+
+use crate::{
+  auth::AccessPolicy,
+  configs::consts,
+  quote::{QuoteGenerator, QuoteSource},
+  server::Server,
+};
+
+/// Server intervals and timeouts, `consts` values by default
+#[derive(Debug, Clone, Copy)]
+pub struct Timings {
+  /// Interval between generated quote lists
+  pub quotes_interval: Duration,
+  /// Client heartbeat interval unless proposed by client
+  pub heartbeat_interval: Duration,
+  /// Eviction timeout without client heartbeats unless proposed by client
+  pub healthcheck_timeout: Duration,
+  /// Interval between health check monitor passes
+  pub healthcheck_monitor_interval: Duration,
+  /// Subscriptions with address not verified in time are dropped
+  pub ownership_verification_timeout: Duration,
+  pub challenge_retry_interval: Duration,
+  /// Server heartbeat is sent when no datagram was sent within the interval
+  pub server_heartbeat_interval: Duration,
+  /// Client workers not drained in time on shutdown are left detached
+  pub shutdown_drain_timeout: Duration,
+}
+
+impl Default for Timings {
+  fn default() -> Self {
+    Self {
+      quotes_interval: consts::QUOTES_GENERATION_TIMEOUT,
+      heartbeat_interval: consts::HEARTBEAT_INTERVAL,
+      healthcheck_timeout: consts::HEALTHCHECK_TIMEOUT,
+      healthcheck_monitor_interval: consts::HEALTH_CHECK_MONITOR_TIMEOUT,
+      ownership_verification_timeout: consts::OWNERSHIP_VERIFICATION_TIMEOUT,
+      challenge_retry_interval: consts::CHALLENGE_RETRY_INTERVAL,
+      server_heartbeat_interval: consts::SERVER_HEARTBEAT_INTERVAL,
+      shutdown_drain_timeout: consts::SHUTDOWN_DRAIN_TIMEOUT,
+    }
+  }
+}
+
+/// Bound server addresses
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub struct LocalAddrs {
+  /// Control listener address
+  pub tcp: SocketAddr,
+  /// Quotes and health check socket address
+  pub udp: SocketAddr,
+}
+
+/// # Server builder
+///
+/// Configures an embeddable quote server. Addresses default to the CLI ones,
+/// port `0` binds an ephemeral port, which is reported by the handle.
+/// Quotes are generated for server tickers unless another source is set.
+///
+/// ```no_run
+/// use std::time::Duration;
+///
+/// use common::error::AppError;
+/// use quote_server::{ServerBuilder, Timings};
+///
+/// fn main() -> Result<(), AppError> {
+///   let server = ServerBuilder::new(vec!["AAPL".into(), "TSLA".into()])
+///     .tcp_addr("127.0.0.1:0".parse().unwrap())
+///     .udp_addr("127.0.0.1:0".parse().unwrap())
+///     .timings(Timings {
+///       quotes_interval: Duration::from_millis(100),
+///       ..Timings::default()
+///     })
+///     .spawn()?;
+///
+///   println!("Listening on {}", server.local_addrs().tcp);
+///
+///   server.shutdown()
+/// }
+/// ```
+pub struct ServerBuilder {
+  pub(crate) tcp_addr: SocketAddr,
+  pub(crate) udp_addr: SocketAddr,
+  pub(crate) tickers: Vec<String>,
+  pub(crate) groups: HashMap<String, Vec<String>>,
+  pub(crate) policy: AccessPolicy,
+  pub(crate) tls: Option<Arc<rustls::ServerConfig>>,
+  pub(crate) quote_source: Option<Box<dyn QuoteSource>>,
+  pub(crate) timings: Timings,
+  pub(crate) shutdown: Arc<AtomicBool>,
+}
+
+impl ServerBuilder {
+  /// Server of `tickers` with default addresses, policy and timings
+  pub fn new(tickers: Vec<String>) -> Self {
+    Self {
+      tcp_addr: consts::SERVER_TCP_ADDR,
+      udp_addr: consts::SERVER_UPD_ADDR,
+      tickers,
+      groups: HashMap::new(),
+      policy: AccessPolicy::default(),
+      tls: None,
+      quote_source: None,
+      timings: Timings::default(),
+      shutdown: Arc::new(AtomicBool::new(false)),
+    }
+  }
+  pub fn tcp_addr(mut self, addr: SocketAddr) -> Self {
+    self.tcp_addr = addr;
+    self
+  }
+  pub fn udp_addr(mut self, addr: SocketAddr) -> Self {
+    self.udp_addr = addr;
+    self
+  }
+  /// Named ticker groups usable in subscriptions
+  pub fn groups(mut self, groups: HashMap<String, Vec<String>>) -> Self {
+    self.groups = groups;
+    self
+  }
+  /// Access rules and limits
+  pub fn policy(mut self, policy: AccessPolicy) -> Self {
+    self.policy = policy;
+    self
+  }
+  /// Serve control channel over TLS only
+  pub fn tls(mut self, config: Arc<rustls::ServerConfig>) -> Self {
+    self.tls = Some(config);
+    self
+  }
+  pub fn quote_source(mut self, source: impl QuoteSource + 'static) -> Self {
+    self.quote_source = Some(Box::new(source));
+    self
+  }
+  pub fn timings(mut self, timings: Timings) -> Self {
+    self.timings = timings;
+    self
+  }
+  /// External shutdown flag, e.g. registered for termination signals
+  pub fn shutdown(mut self, shutdown: Arc<AtomicBool>) -> Self {
+    self.shutdown = shutdown;
+    self
+  }
+  /// Serve in the current thread until shutdown
+  pub fn run(self) -> Result<(), AppError> {
+    let (server, quote_source) = self.bind()?;
+
+    server.run(quote_source)
+  }
+  /// Serve in a background thread, addresses are bound before return
+  pub fn spawn(self) -> Result<ServerHandle, AppError> {
+    let shutdown = Arc::clone(&self.shutdown);
+    let (server, quote_source) = self.bind()?;
+    let local_addrs = server.local_addrs()?;
+    let thread = thread::spawn(move || server.run(quote_source));
+
+    Ok(ServerHandle {
+      local_addrs,
+      shutdown,
+      thread: Some(thread),
+    })
+  }
+  fn bind(mut self) -> Result<(Server, Box<dyn QuoteSource>), AppError> {
+    let quote_source = self
+      .quote_source
+      .take()
+      .unwrap_or_else(|| Box::new(QuoteGenerator::new(&self.tickers)));
+    let server = Server::new(self)?;
+    let local_addrs = server.local_addrs()?;
+
+    info!(
+      tcp = %local_addrs.tcp,
+      udp = %local_addrs.udp,
+      "Initialized server"
+    );
+
+    Ok((server, quote_source))
+  }
+}
+
+/// Running server, which is shut down when dropped
+#[derive(Debug)]
+pub struct ServerHandle {
+  local_addrs: LocalAddrs,
+  shutdown: Arc<AtomicBool>,
+  thread: Option<JoinHandle<Result<(), AppError>>>,
+}
+
+impl ServerHandle {
+  pub fn local_addrs(&self) -> LocalAddrs {
+    self.local_addrs
+  }
+  pub fn is_finished(&self) -> bool {
+    self.thread.as_ref().is_none_or(JoinHandle::is_finished)
+  }
+  /// Shut down and wait for subscribers to be drained
+  pub fn shutdown(self) -> Result<(), AppError> {
+    self.shutdown.store(true, Ordering::Release);
+    self.join()
+  }
+  /// Wait for server to stop, e.g. on shutdown flag set by a signal
+  pub fn join(mut self) -> Result<(), AppError> {
+    match self.thread.take() {
+      Some(thread) => thread.join().map_err(|_| {
+        AppError::OtherError(anyhow!("Failed waiting for server thread"))
+      })?,
+      None => Ok(()),
+    }
+  }
+}
+
+impl Drop for ServerHandle {
+  fn drop(&mut self) {
+    if self.thread.is_some() {
+      self.shutdown.store(true, Ordering::Release);
+    }
+  }
+}
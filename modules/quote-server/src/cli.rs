@@ -0,0 +1,54 @@
+use std::path::PathBuf;
+
+use clap::Parser;
+
+use common::utils::{path_validation, pem_path_validation, rate_validation};
+use quote_server::consts;
+
+#[derive(Debug, Parser)]
+#[command(version, about, next_line_help = true)]
+pub(crate) struct CliArgs {
+  #[arg(short = 'f', long, value_name = "Tickers file", value_parser = path_validation)]
+  pub tickers_file: PathBuf,
+  #[arg(short = 'g', long, value_name = "Ticker groups file", value_parser = path_validation)]
+  pub groups_file: Option<PathBuf>,
+  /// Reject subscriptions with UDP address IP other than the client TCP peer IP
+  #[arg(long)]
+  pub require_same_ip: bool,
+  /// Users credentials file, enables authentication
+  #[arg(long, value_name = "Credentials file", value_parser = path_validation)]
+  pub credentials_file: Option<PathBuf>,
+  /// Users entitlements file, requires credentials file
+  #[arg(long, value_name = "Entitlements file", value_parser = path_validation, requires = "credentials_file")]
+  pub entitlements_file: Option<PathBuf>,
+  /// TLS certificate chain of the TCP listener, enables TLS
+  #[arg(long, value_name = "PEM file", value_parser = pem_path_validation, requires = "tls_key")]
+  pub tls_cert: Option<PathBuf>,
+  /// TLS private key of the TCP listener
+  #[arg(long, value_name = "PEM file", value_parser = pem_path_validation, requires = "tls_cert")]
+  pub tls_key: Option<PathBuf>,
+  /// CA certificates to verify required client certificates
+  #[arg(long, value_name = "PEM file", value_parser = pem_path_validation, requires = "tls_cert")]
+  pub tls_client_ca: Option<PathBuf>,
+  /// Maximum active subscriptions of all clients
+  #[arg(long, value_name = "Subscriptions", default_value_t = consts::MAX_SUBSCRIBERS)]
+  pub max_subscribers: usize,
+  /// Maximum active subscriptions requested from the same client IP
+  #[arg(long, value_name = "Subscriptions", default_value_t = consts::MAX_SUBSCRIPTIONS_PER_IP)]
+  pub max_subscriptions_per_ip: usize,
+  /// Maximum connections accepted from the same IP per minute
+  #[arg(long, value_name = "Connections", default_value_t = consts::MAX_CONNECTIONS_PER_IP)]
+  pub max_connections_per_ip: usize,
+  /// Maximum connections accepted from all clients per second
+  #[arg(long, value_name = "Connections per second", value_parser = rate_validation, default_value_t = consts::HANDSHAKE_RATE)]
+  pub handshake_rate: f64,
+  /// Minimum client heartbeat interval in milliseconds
+  #[arg(long, value_name = "Milliseconds", default_value_t = consts::MIN_HEARTBEAT_INTERVAL)]
+  pub min_heartbeat_interval: u64,
+  /// Maximum client heartbeat interval in milliseconds
+  #[arg(long, value_name = "Milliseconds", default_value_t = consts::MAX_HEARTBEAT_INTERVAL)]
+  pub max_heartbeat_interval: u64,
+  /// Maximum eviction timeout without client heartbeats in milliseconds
+  #[arg(long, value_name = "Milliseconds", default_value_t = consts::MAX_HEARTBEAT_TIMEOUT)]
+  pub max_heartbeat_timeout: u64,
+}
@@ -1,17 +1,4 @@
-use std::path::PathBuf;
-
-use clap::Parser;
-
-use common::utils::path_validation;
-
-#[derive(Debug, Parser)]
-#[command(version, about, next_line_help = true)]
-pub(crate) struct CliArgs {
-  #[arg(short = 'f', long, value_name = "Tickers file", value_parser = path_validation)]
-  pub tickers_file: PathBuf,
-}
-
-pub(crate) mod consts {
+pub mod consts {
   use std::net::{IpAddr, Ipv4Addr, SocketAddr};
   use std::time::Duration;
 
@@ -20,8 +7,34 @@ pub(crate) mod consts {
   pub const SERVER_UPD_ADDR: SocketAddr = SocketAddr::new(SERVER_IP_ADDR, 8001);
   pub const QUOTES_GENERATION_TIMEOUT: Duration = Duration::from_secs(1);
   pub const HEALTHCHECK_TIMEOUT: Duration = Duration::from_secs(5);
+  pub const HEARTBEAT_INTERVAL: Duration = Duration::from_secs(1);
+  pub const HEARTBEAT_TIMEOUT_INTERVALS: u32 = 3;
+  pub const MIN_HEARTBEAT_INTERVAL: u64 = 250;
+  pub const MAX_HEARTBEAT_INTERVAL: u64 = 5000;
+  pub const MAX_HEARTBEAT_TIMEOUT: u64 = 30000;
   pub const UDP_WRITE_TIMEOUT: Duration = Duration::from_secs(5);
+  // Health check server checks for shutdown between reads
+  pub const UDP_READ_TIMEOUT: Duration = Duration::from_millis(200);
+  // Whole control handshake: TLS, request and response
+  pub const HANDSHAKE_TIMEOUT: Duration = Duration::from_secs(3);
+  pub const MAX_REQUEST_BYTES: u64 = 64 * 1024;
+  // Control connections handshaking at once, each on its own thread
+  pub const MAX_PENDING_HANDSHAKES: usize = 64;
   pub const TCP_STREAM_IDLE_TIMEOUT: Duration = Duration::from_millis(50);
   pub const HEALTH_CHECK_MONITOR_TIMEOUT: Duration = Duration::from_millis(50);
   pub const QUOTE_DEFAULT_PRICE: f64 = 1.0;
+  pub const DELTA_FULL_REFRESH_INTERVAL: Duration = Duration::from_secs(10);
+  pub const OWNERSHIP_VERIFICATION_TIMEOUT: Duration = Duration::from_secs(3);
+  pub const CHALLENGE_RETRY_INTERVAL: Duration = Duration::from_millis(500);
+  pub const MAX_SUBSCRIBERS: usize = 1024;
+  pub const MAX_SUBSCRIPTIONS_PER_IP: usize = 32;
+  pub const MAX_CONNECTIONS_PER_IP: usize = 60;
+  pub const CONNECTION_WINDOW: Duration = Duration::from_secs(60);
+  pub const HANDSHAKE_RATE: f64 = 50.0;
+  pub const SERVER_HEARTBEAT_INTERVAL: Duration = Duration::from_secs(1);
+  pub const SHUTDOWN_DRAIN_TIMEOUT: Duration = Duration::from_secs(2);
+  pub const SHUTDOWN_POLL_INTERVAL: Duration = Duration::from_millis(10);
+  // Rejected client datagrams are logged as counts on interval
+  pub const REJECTED_DATAGRAMS_REPORT_INTERVAL: Duration =
+    Duration::from_secs(10);
 }
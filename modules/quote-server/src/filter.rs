@@ -0,0 +1,297 @@
+use std::collections::{HashMap, HashSet};
+
+use regex::Regex;
+
+use common::{error::AppError, stock::SubscriptionReport};
+
+/// Ticker subscription pattern
+///
+/// - `*` matches every ticker
+/// - `A*` matches tickers starting with `A`
+/// - `/^GOO.*$/` matches tickers with the regular expression
+/// - `TECH` matches members of the server-side group with the same name
+/// - anything else matches the ticker exactly
+#[derive(Debug)]
+pub(crate) enum TickerPattern {
+  All,
+  Exact(String),
+  Prefix(String),
+  Regex(Regex),
+  Group(String),
+}
+
+impl TickerPattern {
+  pub fn parse(
+    pattern: &str,
+    groups: &HashMap<String, Vec<String>>,
+  ) -> Result<Self, AppError> {
+    let pattern = pattern.trim();
+
+    if pattern == "*" {
+      return Ok(Self::All);
+    }
+
+    if let Some(expr) = pattern
+      .strip_prefix('/')
+      .and_then(|rest| rest.strip_suffix('/'))
+    {
+      return Regex::new(expr).map(Self::Regex).map_err(|err| {
+        AppError::InvalidTickerPattern {
+          pattern: pattern.to_string(),
+          reason: err.to_string(),
+        }
+      });
+    }
+
+    if let Some(prefix) = pattern.strip_suffix('*') {
+      if prefix.contains('*') {
+        return Err(AppError::InvalidTickerPattern {
+          pattern: pattern.to_string(),
+          reason: "wildcard is only supported at the end".to_string(),
+        });
+      }
+      return Ok(Self::Prefix(prefix.to_string()));
+    }
+
+    if groups.contains_key(pattern) {
+      return Ok(Self::Group(pattern.to_string()));
+    }
+
+    Ok(Self::Exact(pattern.to_string()))
+  }
+  fn matches(&self, ticker: &str) -> bool {
+    match self {
+      Self::All => true,
+      Self::Exact(exact) => exact == ticker,
+      Self::Prefix(prefix) => ticker.starts_with(prefix.as_str()),
+      Self::Regex(regex) => regex.is_match(ticker),
+      // Groups are expanded into member patterns on compilation
+      Self::Group(_) => false,
+    }
+  }
+}
+
+/// Subscription filter compiled against the server tickers list
+///
+/// Patterns are resolved once on subscription, so filtering a generated quote
+/// is a single hash lookup regardless of the patterns count.
+#[derive(Debug, Default)]
+pub(crate) struct TickerFilter {
+  tickers: HashSet<String>,
+  unknown: Vec<String>,
+  denied: Vec<String>,
+}
+
+impl TickerFilter {
+  pub fn compile(
+    patterns: &[String],
+    universe: &[String],
+    groups: &HashMap<String, Vec<String>>,
+  ) -> Result<Self, AppError> {
+    let mut tickers = HashSet::new();
+    let mut unknown = vec![];
+
+    for pattern in patterns {
+      let matched: Vec<String> = match TickerPattern::parse(pattern, groups)? {
+        TickerPattern::Group(name) => {
+          let mut matched = vec![];
+          // Group members are patterns too, nested groups are not expanded
+          for member in &groups[&name] {
+            let member = TickerPattern::parse(member, &HashMap::new())?;
+            matched.extend(Self::resolve(&member, universe));
+          }
+          matched
+        }
+        pattern => Self::resolve(&pattern, universe).collect(),
+      };
+
+      if matched.is_empty() {
+        unknown.push(pattern.trim().to_string());
+      }
+      tickers.extend(matched);
+    }
+
+    Ok(Self {
+      tickers,
+      unknown,
+      denied: vec![],
+    })
+  }
+  /// Keep tickers matched by `entitled` filter, other tickers are denied
+  pub fn restrict(&mut self, entitled: &TickerFilter) {
+    let (allowed, denied): (HashSet<String>, HashSet<String>) = self
+      .tickers
+      .drain()
+      .partition(|ticker| entitled.matches(ticker));
+
+    self.tickers = allowed;
+    self.denied = denied.into_iter().collect();
+    self.denied.sort();
+  }
+  fn resolve<'a>(
+    pattern: &'a TickerPattern,
+    universe: &'a [String],
+  ) -> impl Iterator<Item = String> + 'a {
+    universe
+      .iter()
+      .filter(|ticker| pattern.matches(ticker))
+      .cloned()
+  }
+  pub fn matches(&self, ticker: &str) -> bool {
+    self.tickers.contains(ticker)
+  }
+  pub fn len(&self) -> usize {
+    self.tickers.len()
+  }
+  /// Matched tickers removed by entitlements
+  pub fn denied(&self) -> &[String] {
+    &self.denied
+  }
+  /// Requested patterns which matched no tickers
+  pub fn unknown(&self) -> &[String] {
+    &self.unknown
+  }
+  pub fn report(&self, universe: &[String]) -> SubscriptionReport {
+    let mut accepted: Vec<String> = self.tickers.iter().cloned().collect();
+    accepted.sort();
+
+    SubscriptionReport {
+      accepted,
+      unknown: self.unknown.clone(),
+      denied: self.denied.clone(),
+      suggestions: self
+        .unknown
+        .iter()
+        .map(|pattern| (pattern.clone(), suggest(pattern, universe)))
+        .filter(|(_, suggestions)| !suggestions.is_empty())
+        .collect(),
+    }
+  }
+}
+
+const MAX_SUGGESTIONS: usize = 3;
+
+/// Closest tickers by edit distance, a typo per 3 characters is tolerated
+fn suggest(pattern: &str, universe: &[String]) -> Vec<String> {
+  let pattern = pattern.to_uppercase();
+  let max_distance = pattern.len().div_ceil(3);
+
+  let mut candidates: Vec<(usize, &String)> = universe
+    .iter()
+    .map(|ticker| (edit_distance(&pattern, ticker), ticker))
+    .filter(|(distance, _)| *distance <= max_distance)
+    .collect();
+  candidates.sort();
+
+  candidates
+    .into_iter()
+    .take(MAX_SUGGESTIONS)
+    .map(|(_, ticker)| ticker.clone())
+    .collect()
+}
+
+/// Levenshtein distance between two strings
+fn edit_distance(a: &str, b: &str) -> usize {
+  let b: Vec<char> = b.chars().collect();
+  let mut row: Vec<usize> = (0..=b.len()).collect();
+
+  for (i, a_char) in a.chars().enumerate() {
+    let mut diagonal = row[0];
+    row[0] = i + 1;
+
+    for (j, b_char) in b.iter().enumerate() {
+      let substitution = diagonal + usize::from(a_char != *b_char);
+      diagonal = row[j + 1];
+      row[j + 1] = substitution.min(row[j] + 1).min(diagonal + 1);
+    }
+  }
+
+  row[b.len()]
+}
+
+#[cfg(test)]
+mod tests {
+  use super::*;
+
+  fn universe() -> Vec<String> {
+    ["AAPL", "AMZN", "GOOGL", "MSFT", "TSLA"]
+      .map(String::from)
+      .to_vec()
+  }
+
+  fn groups() -> HashMap<String, Vec<String>> {
+    HashMap::from([(
+      "TECH".to_string(),
+      vec!["A*".to_string(), "MSFT".to_string(), "TECH".to_string()],
+    )])
+  }
+
+  #[test]
+  fn patterns_are_parsed() {
+    let groups = groups();
+
+    assert!(matches!(
+      TickerPattern::parse(" * ", &groups),
+      Ok(TickerPattern::All)
+    ));
+    assert!(matches!(
+      TickerPattern::parse("A*", &groups),
+      Ok(TickerPattern::Prefix(prefix)) if prefix == "A"
+    ));
+    assert!(matches!(
+      TickerPattern::parse("/^GOO.*$/", &groups),
+      Ok(TickerPattern::Regex(regex)) if regex.is_match("GOOGL")
+    ));
+    assert!(matches!(
+      TickerPattern::parse("TECH", &groups),
+      Ok(TickerPattern::Group(name)) if name == "TECH"
+    ));
+    assert!(matches!(
+      TickerPattern::parse("TSLA", &groups),
+      Ok(TickerPattern::Exact(ticker)) if ticker == "TSLA"
+    ));
+  }
+
+  #[test]
+  fn invalid_patterns_are_rejected() {
+    for pattern in ["*A*", "A**", "/[/"] {
+      assert!(
+        matches!(
+          TickerPattern::parse(pattern, &HashMap::new()),
+          Err(AppError::InvalidTickerPattern { .. })
+        ),
+        "{pattern} is accepted"
+      );
+    }
+  }
+
+  #[test]
+  fn groups_are_expanded_once() {
+    let patterns = vec!["TECH".to_string(), "NOPE".to_string()];
+    let filter =
+      TickerFilter::compile(&patterns, &universe(), &groups()).unwrap();
+
+    let report = filter.report(&universe());
+    // Nested `TECH` member matches the exact ticker only
+    assert_eq!(report.accepted, ["AAPL", "AMZN", "MSFT"]);
+    assert_eq!(report.unknown, ["NOPE"]);
+    assert!(!filter.matches("TSLA"));
+  }
+
+  #[test]
+  fn near_matches_are_suggested() {
+    assert_eq!(suggest("aapl", &universe()), ["AAPL"]);
+    assert_eq!(suggest("TSL", &universe()), ["TSLA"]);
+    assert!(suggest("XYZ", &universe()).is_empty());
+  }
+
+  #[test]
+  fn edit_distance_counts_single_char_edits() {
+    assert_eq!(edit_distance("", ""), 0);
+    assert_eq!(edit_distance("", "ABC"), 3);
+    assert_eq!(edit_distance("AAPL", "AAPL"), 0);
+    assert_eq!(edit_distance("APPL", "AAPL"), 1);
+    assert_eq!(edit_distance("GOGL", "GOOGL"), 1);
+    assert_eq!(edit_distance("KITTEN", "SITTING"), 3);
+  }
+}
@@ -0,0 +1,20 @@
+//! This is the stock quote server crate, which streams generated stock quotes
+//! to subscribed clients.
+//!
+//! [`ServerBuilder`] configures an embeddable server, which runs in the current
+//! thread or in background with a [`ServerHandle`].
+
+mod auth;
+mod builder;
+pub mod configs;
+mod filter;
+mod limits;
+mod quote;
+mod server;
+mod subscription;
+
+pub use auth::{AccessPolicy, UserStore};
+pub use builder::{LocalAddrs, ServerBuilder, ServerHandle, Timings};
+pub use configs::consts;
+pub use limits::Limits;
+pub use quote::{QuoteGenerator, QuoteSource};
@@ -0,0 +1,196 @@
+use std::{
+  collections::{HashMap, VecDeque},
+  net::{IpAddr, SocketAddr},
+  time::{Duration, Instant},
+};
+
+use common::{session::HeartbeatPolicy, stock::SubscriptionOptions};
+
+use crate::configs::consts;
+
+/// Control listener and subscription limits
+#[derive(Debug, Clone)]
+pub struct Limits {
+  /// Active subscriptions of all clients
+  pub max_subscribers: usize,
+  /// Active subscriptions requested from the same client IP
+  pub max_subscriptions_per_ip: usize,
+  /// Connections accepted from the same IP per connection window
+  pub max_connections_per_ip: usize,
+  /// Connections accepted from all clients per second
+  pub handshake_rate: f64,
+  /// Bounds of client heartbeat interval
+  pub min_heartbeat_interval: Duration,
+  pub max_heartbeat_interval: Duration,
+  /// Upper bound of eviction timeout without heartbeats
+  pub max_heartbeat_timeout: Duration,
+}
+
+impl Limits {
+  /// Heartbeat terms proposed by client clamped to server limits, defaults
+  /// apply to terms not proposed. Eviction timeout spans several heartbeat
+  /// intervals, so a few lost heartbeats don't evict the client.
+  pub fn negotiate_heartbeat(
+    &self,
+    options: &SubscriptionOptions,
+    default_interval: Duration,
+    default_timeout: Duration,
+  ) -> HeartbeatPolicy {
+    let interval = options
+      .heartbeat_interval_ms
+      .map(Duration::from_millis)
+      .unwrap_or(default_interval)
+      .clamp(
+        self.min_heartbeat_interval,
+        self.max_heartbeat_interval.max(self.min_heartbeat_interval),
+      );
+    let min_timeout = interval * consts::HEARTBEAT_TIMEOUT_INTERVALS;
+    let timeout = options
+      .heartbeat_timeout_ms
+      .map(Duration::from_millis)
+      .unwrap_or(default_timeout)
+      .clamp(min_timeout, self.max_heartbeat_timeout.max(min_timeout));
+
+    HeartbeatPolicy {
+      interval_ms: interval.as_millis() as u64,
+      timeout_ms: timeout.as_millis() as u64,
+    }
+  }
+}
+
+impl Default for Limits {
+  fn default() -> Self {
+    Self {
+      max_subscribers: consts::MAX_SUBSCRIBERS,
+      max_subscriptions_per_ip: consts::MAX_SUBSCRIPTIONS_PER_IP,
+      max_connections_per_ip: consts::MAX_CONNECTIONS_PER_IP,
+      handshake_rate: consts::HANDSHAKE_RATE,
+      min_heartbeat_interval: Duration::from_millis(
+        consts::MIN_HEARTBEAT_INTERVAL,
+      ),
+      max_heartbeat_interval: Duration::from_millis(
+        consts::MAX_HEARTBEAT_INTERVAL,
+      ),
+      max_heartbeat_timeout: Duration::from_millis(
+        consts::MAX_HEARTBEAT_TIMEOUT,
+      ),
+    }
+  }
+}
+
+/// Admission of control connections and subscriptions
+///
+/// Connections are limited per IP within a sliding window and globally with
+/// a token bucket, so bursts up to one second of handshakes are allowed.
+/// Only admitted connections are counted.
+#[derive(Debug)]
+pub(crate) struct Admission {
+  limits: Limits,
+  handshake_tokens: f64,
+  last_refill: Instant,
+  connections: HashMap<IpAddr, VecDeque<Instant>>,
+}
+
+impl Admission {
+  pub fn new(limits: Limits) -> Self {
+    Self {
+      handshake_tokens: limits.handshake_rate.max(1.0),
+      last_refill: Instant::now(),
+      connections: HashMap::new(),
+      limits,
+    }
+  }
+  /// Refusal reason for a new connection from `ip`
+  pub fn admit_connection(
+    &mut self,
+    ip: IpAddr,
+    now: Instant,
+  ) -> Result<(), &'static str> {
+    // Forget connections out of the window, including idle IPs
+    self.connections.retain(|_, accepted| {
+      while accepted.front().is_some_and(|accepted_at| {
+        now.duration_since(*accepted_at) > consts::CONNECTION_WINDOW
+      }) {
+        accepted.pop_front();
+      }
+      !accepted.is_empty()
+    });
+
+    if self.connections.get(&ip).is_some_and(|accepted| {
+      accepted.len() >= self.limits.max_connections_per_ip
+    }) {
+      return Err("Too many connections from client IP, retry later");
+    }
+
+    let elapsed = now.duration_since(self.last_refill).as_secs_f64();
+    self.last_refill = now;
+    self.handshake_tokens = (self.handshake_tokens
+      + elapsed * self.limits.handshake_rate)
+      .min(self.limits.handshake_rate.max(1.0));
+
+    if self.handshake_tokens < 1.0 {
+      return Err("Server is busy, retry later");
+    }
+
+    self.handshake_tokens -= 1.0;
+    self.connections.entry(ip).or_default().push_back(now);
+
+    Ok(())
+  }
+  /// Refusal reason for a new subscription to `addr` requested from
+  /// `peer_ip`, `active` are subscribed addresses with their client IPs.
+  /// Resubscription of an active address replaces it and is not limited.
+  pub fn admit_subscription<'a>(
+    &self,
+    addr: SocketAddr,
+    peer_ip: IpAddr,
+    active: impl ExactSizeIterator<Item = (&'a SocketAddr, IpAddr)> + Clone,
+  ) -> Result<(), &'static str> {
+    if active.clone().any(|(active_addr, _)| *active_addr == addr) {
+      return Ok(());
+    }
+
+    if active.len() >= self.limits.max_subscribers {
+      return Err("Subscriber limit reached");
+    }
+
+    // Keyed on the client IP, UDP addresses are chosen by clients
+    let same_ip = active
+      .filter(|(_, active_peer_ip)| *active_peer_ip == peer_ip)
+      .count();
+    if same_ip >= self.limits.max_subscriptions_per_ip {
+      return Err("Too many subscriptions from client IP");
+    }
+
+    Ok(())
+  }
+}
+
+#[cfg(test)]
+mod tests {
+  use super::*;
+
+  #[test]
+  fn connections_expire_out_of_window() {
+    let mut admission = Admission::new(Limits {
+      max_connections_per_ip: 2,
+      handshake_rate: 1000.0,
+      ..Limits::default()
+    });
+    let ip = IpAddr::from([127, 0, 0, 1]);
+    let now = Instant::now();
+
+    assert!(admission.admit_connection(ip, now).is_ok());
+    assert!(admission.admit_connection(ip, now).is_ok());
+    assert!(admission.admit_connection(ip, now).is_err());
+    // Other IPs have their own window
+    assert!(
+      admission
+        .admit_connection(IpAddr::from([127, 0, 0, 2]), now)
+        .is_ok()
+    );
+
+    let later = now + consts::CONNECTION_WINDOW + Duration::from_millis(1);
+    assert!(admission.admit_connection(ip, later).is_ok());
+  }
+}
@@ -1,20 +1,34 @@
-use std::{
-  collections::HashMap,
-  time::{SystemTime, UNIX_EPOCH},
-};
+use std::collections::HashMap;
 
 use rand::Rng;
 
-use common::stock::StockQuote;
+use common::{stock::StockQuote, utils::unix_millis};
 
 use crate::configs::consts;
 
+/// Source of server quotes, polled on quotes interval
+pub trait QuoteSource: Send {
+  /// Latest quotes of server tickers
+  fn next_quotes(&mut self) -> Vec<StockQuote>;
+}
+
+impl<F> QuoteSource for F
+where
+  F: FnMut() -> Vec<StockQuote> + Send,
+{
+  fn next_quotes(&mut self) -> Vec<StockQuote> {
+    self()
+  }
+}
+
+/// Random walk of prices shared by all tickers, default quote source
+#[derive(Debug)]
 pub struct QuoteGenerator {
   price_map: HashMap<String, f64>,
 }
 
 impl QuoteGenerator {
-  pub fn new(tickers: &Vec<String>) -> Self {
+  pub fn new(tickers: &[String]) -> Self {
     Self {
       price_map: tickers.iter().map(|val| (val.clone(), 1.0)).collect(),
     }
@@ -34,12 +48,9 @@ impl QuoteGenerator {
 
     StockQuote {
       ticker: ticker.to_string(),
-      price: *last_price,
-      volume,
-      timestamp: SystemTime::now()
-        .duration_since(UNIX_EPOCH)
-        .unwrap()
-        .as_millis() as u64,
+      price: Some(*last_price),
+      volume: Some(volume),
+      timestamp: Some(unix_millis()),
     }
   }
   pub fn shuffle_prices(&mut self) {
@@ -53,8 +64,15 @@ impl QuoteGenerator {
   pub fn generate_quote_list(&self) -> Vec<StockQuote> {
     self
       .price_map
-      .iter()
-      .map(|(key, _)| self.generate_quote(key))
+      .keys()
+      .map(|key| self.generate_quote(key))
       .collect()
   }
 }
+
+impl QuoteSource for QuoteGenerator {
+  fn next_quotes(&mut self) -> Vec<StockQuote> {
+    self.shuffle_prices();
+    self.generate_quote_list()
+  }
+}
@@ -0,0 +1,916 @@
+use anyhow::{Context, anyhow};
+use parking_lot::{Mutex, RwLock};
+use serde_json::json;
+use std::{
+  collections::HashMap,
+  io::{self, BufReader, Read, Write},
+  net::{IpAddr, SocketAddr, TcpListener, TcpStream, UdpSocket},
+  sync::atomic::{AtomicBool, AtomicUsize, Ordering},
+  sync::{Arc, mpsc},
+  thread,
+  time::{Duration, Instant},
+};
+use tracing::{error, info, warn};
+
+use common::{
+  error::AppError,
+  seal::DatagramCipher,
+  session::{ClientDatagram, SessionCredentials, generate_nonce},
+  stock::{
+    StockDatagram, StockQuote, StockRequest, StockResponse,
+    StockResponseStatus, SubscriptionOptions,
+  },
+  tls,
+  utils::read_json,
+};
+
+use crate::{
+  auth::AccessPolicy,
+  builder::{LocalAddrs, ServerBuilder, Timings},
+  configs::consts,
+  filter::TickerFilter,
+  limits::Admission,
+  quote::QuoteSource,
+  subscription::Subscription,
+};
+
+type StockQuoteList = Arc<RwLock<Vec<StockQuote>>>;
+type ClientChannelsMap =
+  Arc<RwLock<HashMap<SocketAddr, mpsc::SyncSender<StockQuoteList>>>>;
+type HealthCheckMap = Arc<RwLock<HashMap<SocketAddr, ClientHealth>>>;
+type ClientWorker = thread::JoinHandle<Result<(), AppError>>;
+
+/// Client activity state, refreshed by signed heartbeats only
+#[derive(Debug)]
+struct ClientHealth {
+  last_seen: Instant,
+  // Negotiated eviction timeout without heartbeats
+  timeout: Duration,
+  // Client IP the subscription was requested from
+  peer_ip: IpAddr,
+  session: SessionCredentials,
+  // Last accepted heartbeat counter, lower counters are replays
+  counter: u64,
+  // Pending address ownership check, `None` once verified
+  challenge: Option<Challenge>,
+  verified: Arc<AtomicBool>,
+}
+
+/// Client datagrams rejected since the last report
+///
+/// Flooded datagrams would flood logs with a warning each, so they are
+/// counted and logged on interval and once more on shutdown.
+#[derive(Debug, Default)]
+struct RejectedDatagrams {
+  malformed: u64,
+  invalid_signature: u64,
+  replayed: u64,
+  last_addr: Option<SocketAddr>,
+}
+
+impl RejectedDatagrams {
+  fn reject(&mut self, addr: SocketAddr) -> &mut Self {
+    self.last_addr = Some(addr);
+    self
+  }
+  /// Log and reset counts, nothing is logged without rejections
+  fn report(&mut self) {
+    let Some(last_addr) = self.last_addr else {
+      return;
+    };
+
+    warn!(
+      malformed = self.malformed,
+      invalid_signature = self.invalid_signature,
+      replayed = self.replayed,
+      last_addr = %last_addr,
+      "Rejected client datagrams:"
+    );
+    *self = Self::default();
+  }
+}
+
+#[derive(Debug)]
+struct Challenge {
+  nonce: String,
+  sent_at: Option<Instant>,
+  deadline: Instant,
+}
+
+#[derive(Debug)]
+pub(crate) struct Server {
+  tcp: TcpListener,
+  udp: UdpSocket,
+  tickers: Vec<String>,
+  groups: HashMap<String, Vec<String>>,
+  policy: AccessPolicy,
+  // Entitlements resolved once on start, by username
+  entitled: Option<HashMap<String, TickerFilter>>,
+  admission: Mutex<Admission>,
+  tls: Option<Arc<rustls::ServerConfig>>,
+  timings: Timings,
+  client_channel_map: ClientChannelsMap,
+  health_check_map: HealthCheckMap,
+  workers: Mutex<Vec<(SocketAddr, ClientWorker)>>,
+  shutdown: Arc<AtomicBool>,
+}
+
+impl Server {
+  /// Bind listener and socket of builder addresses
+  pub fn new(builder: ServerBuilder) -> Result<Self, AppError> {
+    let ServerBuilder {
+      tcp_addr,
+      udp_addr,
+      tickers,
+      groups,
+      policy,
+      tls,
+      timings,
+      shutdown,
+      ..
+    } = builder;
+
+    let tcp_listener = TcpListener::bind(tcp_addr).map_err(|err| {
+      AppError::AddressBindError {
+        err,
+        addr: tcp_addr,
+      }
+    })?;
+    tcp_listener
+      .set_nonblocking(true)
+      .map_err(|err| AppError::TcpListenerError { err })?;
+    let udp_socket =
+      UdpSocket::bind(udp_addr).map_err(|err| AppError::AddressBindError {
+        err,
+        addr: udp_addr,
+      })?;
+    udp_socket
+      .set_write_timeout(Some(consts::UDP_WRITE_TIMEOUT))
+      .map_err(|err| AppError::UdpSocketError { err })?;
+
+    let entitled = policy
+      .entitlements
+      .as_ref()
+      .map(|entitlements| {
+        entitlements
+          .iter()
+          .map(|(username, patterns)| {
+            let filter = TickerFilter::compile(patterns, &tickers, &groups)
+              .context(format!("Invalid entitlements of user {username}"))?;
+
+            Ok((username.clone(), filter))
+          })
+          .collect::<Result<HashMap<_, _>, AppError>>()
+      })
+      .transpose()?;
+
+    Ok(Self {
+      tcp: tcp_listener,
+      udp: udp_socket,
+      tickers,
+      groups,
+      entitled,
+      admission: Mutex::new(Admission::new(policy.limits.clone())),
+      policy,
+      tls,
+      timings,
+      client_channel_map: Arc::new(RwLock::new(HashMap::new())),
+      health_check_map: Arc::new(RwLock::new(HashMap::new())),
+      workers: Mutex::new(vec![]),
+      shutdown,
+    })
+  }
+  /// Bound addresses, which differ from builder ones for `0` ports
+  pub fn local_addrs(&self) -> Result<LocalAddrs, AppError> {
+    Ok(LocalAddrs {
+      tcp: self
+        .tcp
+        .local_addr()
+        .map_err(|err| AppError::TcpListenerError { err })?,
+      udp: self
+        .udp
+        .local_addr()
+        .map_err(|err| AppError::UdpSocketError { err })?,
+    })
+  }
+  /// Serve until shutdown, quotes are polled from `quote_source`
+  pub fn run(
+    &self,
+    quote_source: Box<dyn QuoteSource>,
+  ) -> Result<(), AppError> {
+    info!("Run server");
+
+    let (tx, rx) = mpsc::sync_channel::<StockQuoteList>(1);
+    let quotes_broadcasting = self.broadcast_quotes_to_channels(rx);
+    let healthcheck_server = self.start_healthcheck_server()?;
+    let healthcheck_monitoring = self.start_healthcheck_monitoring()?;
+    let quotes_generation_thread =
+      self.start_quotes_generation(quote_source, tx);
+    self.start_tcp_server()?;
+    self.drain_subscribers();
+
+    let _ = quotes_generation_thread.join().map_err(|_| {
+      AppError::OtherError(anyhow!(
+        "Failed waiting for quotes generation thread"
+      ))
+    })?;
+    let _ = healthcheck_monitoring.join().map_err(|_| {
+      AppError::OtherError(anyhow!(
+        "Failed waiting for healthcheck_monitoring thread"
+      ))
+    })?;
+    let _ = healthcheck_server.join().map_err(|_| {
+      AppError::OtherError(anyhow!("Failed waiting for healthcheck thread"))
+    })?;
+    quotes_broadcasting.join().map_err(|_| {
+      AppError::OtherError(anyhow!(
+        "Failed waiting for quotes broadcasting thread"
+      ))
+    })?;
+
+    Ok(())
+  }
+  /* quote list broadcasting to spawned threads */
+  fn broadcast_quotes_to_channels(
+    &self,
+    rx: mpsc::Receiver<StockQuoteList>,
+  ) -> thread::JoinHandle<()> {
+    info!("Start quotes broadcasting");
+
+    let client_channel_map = Arc::clone(&self.client_channel_map);
+
+    thread::spawn(move || {
+      while let Ok(msg) = rx.recv() {
+        let client_channel_map = client_channel_map.read();
+
+        for (_, tx) in client_channel_map.iter() {
+          match tx.send(Arc::clone(&msg)) {
+            Ok(_) => {
+              // message sent, continue receiving messages
+            }
+            Err(e) => {
+              error!(err = %e, "Failed sending message to channel");
+            }
+          }
+        }
+      }
+    })
+  }
+  fn start_quotes_generation(
+    &self,
+    mut quote_source: Box<dyn QuoteSource>,
+    tx: mpsc::SyncSender<StockQuoteList>,
+  ) -> thread::JoinHandle<Result<(), AppError>> {
+    info!("Start quotes generation");
+
+    // Share Arc<RwLock> reference to avoid data cloning on message dispatch
+    let quotes_list: StockQuoteList = Arc::new(RwLock::new(vec![]));
+    let shutdown = Arc::clone(&self.shutdown);
+    let quotes_interval = self.timings.quotes_interval;
+
+    thread::spawn(move || -> Result<(), AppError> {
+      while !shutdown.load(Ordering::Acquire) {
+        let new_quotes_list = quote_source.next_quotes();
+        {
+          let mut mut_quotes_list = quotes_list.write();
+          *mut_quotes_list = new_quotes_list
+        }
+
+        tx.send(Arc::clone(&quotes_list))
+          .context("Failed sending list of generated quotes")?;
+        thread::sleep(quotes_interval);
+      }
+
+      Ok(())
+    })
+  }
+  fn start_tcp_server(&self) -> Result<(), AppError> {
+    info!("Start TCP server");
+    let shutdown = Arc::clone(&self.shutdown);
+    // Only the accept thread admits handshakes, handler threads release them
+    let pending = AtomicUsize::new(0);
+
+    // Handshakes run on scoped threads, so a slow peer holds only its own
+    // connection, and all of them end before subscribers are drained
+    thread::scope(|scope| {
+      for stream in self.tcp.incoming() {
+        if shutdown.load(Ordering::Acquire) {
+          return Ok(());
+        }
+
+        match stream {
+          Ok(stream) => {
+            // Failed requests must not stop the listener
+            if let Err(e) = stream.set_nodelay(true) {
+              warn!(err = %e, "Failed handling TCP request");
+              continue;
+            }
+            let stream = DeadlineStream::new(
+              stream,
+              Instant::now() + consts::HANDSHAKE_TIMEOUT,
+            );
+
+            match self.admit_connection(stream, pending.load(Ordering::Acquire))
+            {
+              Ok(Some((stream, peer_addr))) => {
+                pending.fetch_add(1, Ordering::AcqRel);
+                let pending = &pending;
+
+                scope.spawn(move || {
+                  if let Err(e) = self.read_tcp_stream(stream, peer_addr) {
+                    warn!(err = %e, "Failed handling TCP request");
+                  }
+                  pending.fetch_sub(1, Ordering::AcqRel);
+                });
+              }
+              Ok(None) => {
+                // connection is refused
+              }
+              Err(e) => {
+                warn!(err = %e, "Failed handling TCP request");
+              }
+            }
+          }
+          Err(ref e) if e.kind() == io::ErrorKind::WouldBlock => {
+            thread::sleep(consts::TCP_STREAM_IDLE_TIMEOUT);
+          }
+          Err(e) => {
+            error!(error = %e, "Connection failed");
+          }
+        }
+      }
+
+      Ok(())
+    })
+  }
+  /// Admitted connection with its peer address, `None` once refused
+  fn admit_connection(
+    &self,
+    stream: DeadlineStream,
+    pending: usize,
+  ) -> Result<Option<(DeadlineStream, SocketAddr)>, AppError> {
+    let peer_addr = stream
+      .stream
+      .peer_addr()
+      .map_err(|err| AppError::TcpStreamError { err })?;
+
+    // Refuse before TLS handshake, which is the expensive part to limit
+    let admitted = if pending >= consts::MAX_PENDING_HANDSHAKES {
+      Err("Server is busy, retry later")
+    } else {
+      self
+        .admission
+        .lock()
+        .admit_connection(peer_addr.ip(), Instant::now())
+    };
+    if let Err(reason) = admitted {
+      warn!(peer = %peer_addr, reason = %reason, "Connection refused");
+
+      // Refusal can't be read by TLS clients without the handshake
+      if self.tls.is_none() {
+        write_response(stream, &StockResponse::retry(reason))?;
+      }
+      return Ok(None);
+    }
+
+    Ok(Some((stream, peer_addr)))
+  }
+  fn read_tcp_stream(
+    &self,
+    stream: DeadlineStream,
+    peer_addr: SocketAddr,
+  ) -> Result<(), AppError> {
+    info!("Read tcp stream!");
+
+    let mut stream = tls::accept(stream, self.tls.as_ref())?;
+
+    // Larger requests are cut short and fail to parse
+    let request =
+      BufReader::new(Read::take(&mut stream, consts::MAX_REQUEST_BYTES));
+    let response = match read_json::<StockRequest>(request) {
+      Ok(request) if request.kind == "STREAM" => {
+        self.subscribe(peer_addr, request)?
+      }
+      Ok(request) => {
+        warn!(kind = %request.kind, "Unsupported command");
+
+        StockResponse::error("Unsupported command")
+      }
+      Err(e) => {
+        warn!(peer = %peer_addr, err = %e, "Malformed request");
+
+        StockResponse::error("Malformed request")
+      }
+    };
+
+    write_response(stream, &response)
+  }
+  fn subscribe(
+    &self,
+    peer_addr: SocketAddr,
+    request: StockRequest,
+  ) -> Result<StockResponse, AppError> {
+    let StockRequest {
+      addr,
+      tickers,
+      options,
+      auth,
+      ..
+    } = request;
+
+    if self.policy.require_same_ip && addr.ip() != peer_addr.ip() {
+      warn!(addr = %addr, peer = %peer_addr, "Foreign UDP address rejected");
+
+      return Ok(StockResponse::error(
+        "UDP address should share the TCP peer IP",
+      ));
+    }
+
+    let username = match (&self.policy.users, &auth) {
+      (None, _) => None,
+      (Some(_), None) => {
+        warn!(peer = %peer_addr, "Unauthenticated subscription rejected");
+
+        return Ok(StockResponse::error("Authentication required"));
+      }
+      (Some(users), Some(credentials)) => {
+        match users.authenticate(credentials) {
+          Some(username) => Some(username),
+          None => {
+            warn!(peer = %peer_addr, user = %credentials.username, "Invalid credentials");
+
+            return Ok(StockResponse::error("Invalid credentials"));
+          }
+        }
+      }
+    };
+
+    let mut filter =
+      match TickerFilter::compile(&tickers, &self.tickers, &self.groups) {
+        Ok(filter) => filter,
+        Err(e) => {
+          warn!(err = %e, "Invalid subscription");
+
+          return Ok(StockResponse::error(e.to_string()));
+        }
+      };
+
+    if options.strict && !filter.unknown().is_empty() {
+      warn!(unknown = ?filter.unknown(), "Strict subscription rejected");
+
+      return Ok(StockResponse {
+        report: Some(filter.report(&self.tickers)),
+        ..StockResponse::error(format!(
+          "Unknown tickers: {}",
+          filter.unknown().join(", ")
+        ))
+      });
+    }
+
+    if let (Some(entitled), Some(username)) = (&self.entitled, username) {
+      let none = TickerFilter::default();
+      filter.restrict(entitled.get(username).unwrap_or(&none));
+
+      if !filter.denied().is_empty() {
+        warn!(user = %username, denied = ?filter.denied(), "Tickers denied");
+      }
+    }
+
+    // Admission is held until the subscription is added, so concurrent
+    // handshakes can't exceed the caps
+    let admission = self.admission.lock();
+    let admitted = admission.admit_subscription(
+      addr,
+      peer_addr.ip(),
+      self
+        .health_check_map
+        .read()
+        .iter()
+        .map(|(addr, health)| (addr, health.peer_ip)),
+    );
+    if let Err(reason) = admitted {
+      warn!(addr = %addr, reason = %reason, "Subscription refused");
+
+      return Ok(StockResponse::retry(reason));
+    }
+
+    let report = filter.report(&self.tickers);
+    let heartbeat = self.policy.limits.negotiate_heartbeat(
+      &options,
+      self.timings.heartbeat_interval,
+      self.timings.healthcheck_timeout,
+    );
+    info!(
+      addr = %addr,
+      interval_ms = heartbeat.interval_ms,
+      timeout_ms = heartbeat.timeout_ms,
+      "Negotiated heartbeat"
+    );
+    let session = SessionCredentials::generate();
+    let cipher = options
+      .encrypt
+      .then(|| DatagramCipher::new(&session))
+      .transpose()?;
+    let verified = Arc::new(AtomicBool::new(false));
+    self.start_quotes_streaming(
+      addr,
+      filter,
+      &options,
+      cipher,
+      Arc::clone(&verified),
+    )?;
+
+    // Add new client to health_check_map, streaming starts when the
+    // challenge nonce sent to subscribed address is echoed back
+    let healthcheck_map = &mut self.health_check_map.write();
+    healthcheck_map.insert(
+      addr,
+      ClientHealth {
+        last_seen: Instant::now(),
+        timeout: heartbeat.timeout(),
+        peer_ip: peer_addr.ip(),
+        session: session.clone(),
+        counter: 0,
+        challenge: Some(Challenge {
+          nonce: generate_nonce(),
+          sent_at: None,
+          deadline: Instant::now()
+            + self.timings.ownership_verification_timeout,
+        }),
+        verified,
+      },
+    );
+
+    Ok(StockResponse {
+      status: StockResponseStatus::Ok,
+      message: "ok".to_string(),
+      report: Some(report),
+      session: Some(session),
+      heartbeat: Some(heartbeat),
+    })
+  }
+  fn start_quotes_streaming(
+    &self,
+    addr: SocketAddr,
+    filter: TickerFilter,
+    options: &SubscriptionOptions,
+    cipher: Option<DatagramCipher>,
+    verified: Arc<AtomicBool>,
+  ) -> Result<(), AppError> {
+    info!(
+      addr = %addr,
+      tickers = filter.len(),
+      interval = ?options.send_interval(),
+      sealed = cipher.is_some(),
+      "Start quotes streaming"
+    );
+
+    let udp = self
+      .udp
+      .try_clone()
+      .map_err(|err| AppError::UdpSocketError { err })?;
+    let (tx, rx) = mpsc::sync_channel::<StockQuoteList>(1);
+    {
+      let client_channel_map = &mut self.client_channel_map.write();
+      client_channel_map.insert(addr, tx);
+    }
+
+    let mut subscription = Subscription::new(
+      filter,
+      options,
+      self.timings.server_heartbeat_interval,
+    );
+    let shutdown = Arc::clone(&self.shutdown);
+
+    let worker = thread::spawn(move || -> Result<(), AppError> {
+      loop {
+        // Wake up for conflated quotes or heartbeat when nothing is generated
+        let received =
+          match subscription.wakeup(verified.load(Ordering::Acquire)) {
+            Some(deadline) => rx
+              .recv_timeout(deadline.saturating_duration_since(Instant::now())),
+            None => rx.recv().map_err(mpsc::RecvTimeoutError::from),
+          };
+
+        match received {
+          Ok(quotes) if verified.load(Ordering::Acquire) => {
+            subscription.push(&quotes.read())
+          }
+          Ok(_) => {
+            // address ownership is not verified yet
+          }
+          Err(mpsc::RecvTimeoutError::Timeout) => {
+            // pending quotes are due
+          }
+          Err(mpsc::RecvTimeoutError::Disconnected) => break,
+        }
+
+        let now = Instant::now();
+        if let Some(datagram) = subscription.take_due(now) {
+          send_datagram(&udp, addr, cipher.as_ref(), &datagram)?;
+        } else if verified.load(Ordering::Acquire) {
+          if let Some(datagram) = subscription.take_heartbeat(now) {
+            send_datagram(&udp, addr, cipher.as_ref(), &datagram)?;
+          }
+        }
+      }
+
+      // Channel is dropped on eviction or shutdown, only verified
+      // subscribers are notified on shutdown
+      if shutdown.load(Ordering::Acquire) && verified.load(Ordering::Acquire) {
+        if let Some(datagram) = subscription.flush(Instant::now()) {
+          send_datagram(&udp, addr, cipher.as_ref(), &datagram)?;
+        }
+
+        let datagram = subscription.going_away("Server is shutting down");
+        send_datagram(&udp, addr, cipher.as_ref(), &datagram)?;
+      }
+
+      Ok(())
+    });
+
+    let workers = &mut self.workers.lock();
+    workers.retain(|(_, worker)| !worker.is_finished());
+    workers.push((addr, worker));
+
+    Ok(())
+  }
+  /// Ordered shutdown of client workers, which flush pending quotes and notify
+  /// subscribers once quote channels are dropped. Workers not finished by the
+  /// drain deadline are left detached.
+  fn drain_subscribers(&self) {
+    let subscribers = {
+      let client_channel_map = &mut self.client_channel_map.write();
+      let subscribers = client_channel_map.len();
+      client_channel_map.clear();
+
+      subscribers
+    };
+    info!(subscribers, "Drain subscribers");
+
+    let deadline = Instant::now() + self.timings.shutdown_drain_timeout;
+    let mut workers = std::mem::take(&mut *self.workers.lock());
+    let mut drained = 0;
+    let mut failed = 0;
+
+    while !workers.is_empty() && Instant::now() < deadline {
+      let finished;
+      (finished, workers) = workers
+        .into_iter()
+        .partition(|(_, worker)| worker.is_finished());
+
+      for (addr, worker) in finished {
+        match worker.join() {
+          Ok(Ok(())) => drained += 1,
+          Ok(Err(e)) => {
+            warn!(addr = %addr, err = %e, "Client worker failed");
+            failed += 1;
+          }
+          Err(_) => {
+            warn!(addr = %addr, "Client worker panicked");
+            failed += 1;
+          }
+        }
+      }
+
+      thread::sleep(consts::SHUTDOWN_POLL_INTERVAL);
+    }
+
+    for (addr, _) in &workers {
+      warn!(addr = %addr, "Client worker is not drained");
+    }
+    info!(
+      subscribers,
+      drained,
+      failed,
+      abandoned = workers.len(),
+      "Shutdown report:"
+    );
+  }
+  fn start_healthcheck_monitoring(
+    &self,
+  ) -> Result<thread::JoinHandle<Result<(), AppError>>, AppError> {
+    let udp = self
+      .udp
+      .try_clone()
+      .map_err(|err| AppError::UdpSocketError { err })?;
+    let health_check_map_lock = Arc::clone(&self.health_check_map);
+    let client_channel_map = Arc::clone(&self.client_channel_map);
+    let shutdown = Arc::clone(&self.shutdown);
+    let Timings {
+      challenge_retry_interval,
+      healthcheck_monitor_interval,
+      ..
+    } = self.timings;
+
+    Ok(thread::spawn(move || {
+      while !shutdown.load(Ordering::Acquire) {
+        {
+          let mut health_check_map = health_check_map_lock.write();
+          let current = Instant::now();
+          let mut remove_list: Vec<SocketAddr> = vec![];
+
+          for (addr, health) in health_check_map.iter_mut() {
+            let diff = current.duration_since(health.last_seen);
+
+            if diff > health.timeout {
+              warn!(addr = %addr, "Client is disconnected:");
+              remove_list.push(*addr);
+              continue;
+            }
+
+            let Some(challenge) = &mut health.challenge else {
+              continue;
+            };
+
+            if current > challenge.deadline {
+              warn!(addr = %addr, "Address ownership is not verified:");
+              remove_list.push(*addr);
+            } else if challenge.sent_at.is_none_or(|sent_at| {
+              current.duration_since(sent_at) > challenge_retry_interval
+            }) {
+              let message = json!(StockDatagram::Challenge {
+                nonce: challenge.nonce.clone(),
+              })
+              .to_string();
+
+              if let Err(e) = udp.send_to(message.as_bytes(), addr) {
+                warn!(addr = %addr, err = %e, "Failed sending challenge");
+              }
+              challenge.sent_at = Some(current);
+            }
+          }
+
+          if !remove_list.is_empty() {
+            let mut client_channel_map = client_channel_map.write();
+
+            for addr in remove_list {
+              health_check_map.remove(&addr);
+              client_channel_map.remove(&addr);
+            }
+          }
+        }
+
+        thread::sleep(healthcheck_monitor_interval);
+      }
+
+      Ok(())
+    }))
+  }
+  fn start_healthcheck_server(
+    &self,
+  ) -> Result<thread::JoinHandle<Result<(), AppError>>, AppError> {
+    let udp = self
+      .udp
+      .try_clone()
+      .map_err(|err| AppError::UdpSocketError { err })?;
+    udp
+      .set_read_timeout(Some(consts::UDP_READ_TIMEOUT))
+      .map_err(|err| AppError::UdpSocketError { err })?;
+    let mut buf = vec![0u8; 512];
+    let health_check_map = Arc::clone(&self.health_check_map);
+    let shutdown = Arc::clone(&self.shutdown);
+
+    Ok(thread::spawn(move || {
+      let mut rejected = RejectedDatagrams::default();
+      let mut reported_at = Instant::now();
+
+      while !shutdown.load(Ordering::Acquire) {
+        if reported_at.elapsed() >= consts::REJECTED_DATAGRAMS_REPORT_INTERVAL {
+          rejected.report();
+          reported_at = Instant::now();
+        }
+
+        match udp.recv_from(&mut buf) {
+          Ok((n, from)) => {
+            let Ok(datagram) =
+              serde_json::from_slice::<ClientDatagram>(&buf[..n])
+            else {
+              rejected.reject(from).malformed += 1;
+              continue;
+            };
+
+            let mut health_check_map = health_check_map.write();
+            let Some(health) = health_check_map.get_mut(&from) else {
+              continue;
+            };
+
+            match datagram {
+              ClientDatagram::Heartbeat(heartbeat) => {
+                if !heartbeat.verify(&health.session) {
+                  rejected.reject(from).invalid_signature += 1;
+                } else if heartbeat.counter <= health.counter {
+                  rejected.reject(from).replayed += 1;
+                } else {
+                  // update client activity timestamp
+                  health.counter = heartbeat.counter;
+                  health.last_seen = Instant::now();
+                }
+              }
+              ClientDatagram::Verification { nonce } => {
+                if health
+                  .challenge
+                  .as_ref()
+                  .is_some_and(|challenge| challenge.nonce == nonce)
+                {
+                  info!(addr = %from, "Address ownership verified");
+                  health.challenge = None;
+                  health.verified.store(true, Ordering::Release);
+                }
+              }
+            }
+          }
+          Err(e)
+            if e.kind() == io::ErrorKind::TimedOut
+              || e.kind() == io::ErrorKind::WouldBlock =>
+          {
+            // skip timeout|blocking read
+          }
+          Err(e) => {
+            rejected.report();
+            return Err(e).context("Failed reading from UDP socket")?;
+          }
+        }
+      }
+      rejected.report();
+
+      Ok(())
+    }))
+  }
+}
+
+fn send_datagram(
+  udp: &UdpSocket,
+  addr: SocketAddr,
+  cipher: Option<&DatagramCipher>,
+  datagram: &StockDatagram,
+) -> Result<(), AppError> {
+  let message = json!(datagram).to_string().into_bytes();
+  let message = match cipher {
+    Some(cipher) => {
+      cipher.seal(datagram.seq().unwrap_or_default(), &message)?
+    }
+    None => message,
+  };
+
+  udp
+    .send_to(&message, addr)
+    .context("Failed sending data to UDP socket")?;
+
+  Ok(())
+}
+
+fn write_response(
+  mut stream: impl Write,
+  response: &StockResponse,
+) -> Result<(), AppError> {
+  let message = json!(response).to_string();
+  stream
+    .write_all(message.as_bytes())
+    .context("Failed writing to TCP stream")?;
+  stream.flush().context("Failed writing data to stream")?;
+
+  Ok(())
+}
+
+/// Control connection, which fails reads and writes once the handshake
+/// deadline passes, so a peer trickling bytes can't hold it longer
+#[derive(Debug)]
+struct DeadlineStream {
+  stream: TcpStream,
+  deadline: Instant,
+}
+
+impl DeadlineStream {
+  fn new(stream: TcpStream, deadline: Instant) -> Self {
+    Self { stream, deadline }
+  }
+  fn remaining(&self) -> io::Result<Duration> {
+    let remaining = self.deadline.saturating_duration_since(Instant::now());
+    if remaining.is_zero() {
+      return Err(io::Error::new(
+        io::ErrorKind::TimedOut,
+        "Handshake deadline passed",
+      ));
+    }
+
+    Ok(remaining)
+  }
+}
+
+impl Read for DeadlineStream {
+  fn read(&mut self, buf: &mut [u8]) -> io::Result<usize> {
+    self.stream.set_read_timeout(Some(self.remaining()?))?;
+    self.stream.read(buf)
+  }
+}
+
+impl Write for DeadlineStream {
+  fn write(&mut self, buf: &[u8]) -> io::Result<usize> {
+    self.stream.set_write_timeout(Some(self.remaining()?))?;
+    self.stream.write(buf)
+  }
+  fn flush(&mut self) -> io::Result<()> {
+    self.stream.flush()
+  }
+}
@@ -0,0 +1,310 @@
+use std::{
+  collections::HashMap,
+  time::{Duration, Instant},
+};
+
+use common::{
+  stock::{QuoteField, StockDatagram, StockQuote, SubscriptionOptions},
+  utils::unix_millis,
+};
+
+use crate::{configs::consts, filter::TickerFilter};
+
+/// Client subscription state, which selects quotes due for sending
+///
+/// Generated quotes are filtered and conflated to the latest quote per ticker,
+/// pending quotes are sent at most once per subscription send interval.
+/// In delta mode only quotes with price or volume changed since the quote last
+/// sent are selected, with a refresh snapshot of the sent quotes on refresh
+/// interval. Quotes are projected to the requested fields on arrival, so only
+/// changes of the requested price or volume are sent.
+/// Heartbeat is due when no datagram was sent within the heartbeat interval.
+#[derive(Debug)]
+pub(crate) struct Subscription {
+  filter: TickerFilter,
+  fields: Option<Vec<QuoteField>>,
+  interval: Option<Duration>,
+  full_refresh: Option<Duration>,
+  pending: HashMap<String, StockQuote>,
+  pending_since: Option<Instant>,
+  last_sent: Option<Instant>,
+  last_snapshot: Option<Instant>,
+  last_datagram: Instant,
+  heartbeat_interval: Duration,
+  // Quotes last sent to client, tracked in delta mode only
+  client_quotes: HashMap<String, StockQuote>,
+  seq: u64,
+}
+
+impl Subscription {
+  pub fn new(
+    filter: TickerFilter,
+    options: &SubscriptionOptions,
+    heartbeat_interval: Duration,
+  ) -> Self {
+    let full_refresh = options.delta.then(|| {
+      options
+        .full_refresh_ms
+        .map(Duration::from_millis)
+        .unwrap_or(consts::DELTA_FULL_REFRESH_INTERVAL)
+    });
+
+    Self {
+      filter,
+      fields: options.fields.clone(),
+      interval: options.send_interval(),
+      full_refresh,
+      pending: HashMap::new(),
+      pending_since: None,
+      last_sent: None,
+      last_snapshot: None,
+      last_datagram: Instant::now(),
+      heartbeat_interval,
+      client_quotes: HashMap::new(),
+      seq: 0,
+    }
+  }
+  pub fn push(&mut self, quotes: &[StockQuote]) {
+    for quote in quotes.iter().filter(|el| self.filter.matches(&el.ticker)) {
+      let mut quote = quote.clone();
+      if let Some(fields) = &self.fields {
+        quote.project(fields);
+      }
+
+      self.pending.insert(quote.ticker.clone(), quote);
+      self.pending_since.get_or_insert_with(Instant::now);
+    }
+  }
+  /// Time when pending quotes are due, `None` when nothing is pending
+  pub fn deadline(&self) -> Option<Instant> {
+    let pending_since = self.pending_since?;
+
+    match (self.last_sent, self.interval) {
+      (Some(last_sent), Some(interval)) => {
+        Some(pending_since.max(last_sent + interval))
+      }
+      _ => Some(pending_since),
+    }
+  }
+  /// Time when quotes or heartbeat are due, heartbeats are skipped until
+  /// subscriber address is verified
+  pub fn wakeup(&self, heartbeats: bool) -> Option<Instant> {
+    let heartbeat =
+      heartbeats.then(|| self.last_datagram + self.heartbeat_interval);
+
+    match (self.deadline(), heartbeat) {
+      (Some(deadline), Some(heartbeat)) => Some(deadline.min(heartbeat)),
+      (deadline, heartbeat) => deadline.or(heartbeat),
+    }
+  }
+  /// Heartbeat datagram, `None` when a datagram was sent recently
+  pub fn take_heartbeat(&mut self, now: Instant) -> Option<StockDatagram> {
+    if now < self.last_datagram + self.heartbeat_interval {
+      return None;
+    }
+
+    self.last_datagram = now;
+    self.seq += 1;
+
+    Some(StockDatagram::Heartbeat { seq: self.seq })
+  }
+  /// Datagram with due quotes, `None` when nothing is due or changed
+  pub fn take_due(&mut self, now: Instant) -> Option<StockDatagram> {
+    if self.deadline().is_none_or(|deadline| deadline > now) {
+      return None;
+    }
+
+    self.take_pending(now)
+  }
+  /// Datagram with pending quotes regardless of the send interval
+  pub fn flush(&mut self, now: Instant) -> Option<StockDatagram> {
+    self.pending_since?;
+
+    self.take_pending(now)
+  }
+  /// Last datagram of the subscription
+  pub fn going_away(&mut self, message: impl Into<String>) -> StockDatagram {
+    self.seq += 1;
+
+    StockDatagram::GoingAway {
+      seq: self.seq,
+      message: message.into(),
+    }
+  }
+  fn take_pending(&mut self, now: Instant) -> Option<StockDatagram> {
+    self.last_sent = Some(now);
+    self.pending_since = None;
+
+    let Some(full_refresh) = self.full_refresh else {
+      self.last_datagram = now;
+      self.seq += 1;
+
+      return Some(StockDatagram::Snapshot {
+        seq: self.seq,
+        sent_at: Some(unix_millis()),
+        refresh: false,
+        quotes: self.pending.drain().map(|(_, quote)| quote).collect(),
+      });
+    };
+
+    let changed: Vec<StockQuote> = self
+      .pending
+      .drain()
+      .filter_map(|(ticker, quote)| {
+        let is_changed = self.client_quotes.get(&ticker).is_none_or(|known| {
+          known.price != quote.price || known.volume != quote.volume
+        });
+        if is_changed {
+          self.client_quotes.insert(ticker, quote.clone());
+        }
+
+        is_changed.then_some(quote)
+      })
+      .collect();
+
+    if self
+      .last_snapshot
+      .is_none_or(|last_snapshot| now >= last_snapshot + full_refresh)
+    {
+      let refresh = self.last_snapshot.is_some();
+      self.last_snapshot = Some(now);
+      self.last_datagram = now;
+      self.seq += 1;
+
+      return Some(StockDatagram::Snapshot {
+        seq: self.seq,
+        sent_at: Some(unix_millis()),
+        refresh,
+        quotes: self.client_quotes.values().cloned().collect(),
+      });
+    }
+
+    if changed.is_empty() {
+      return None;
+    }
+
+    self.last_datagram = now;
+    self.seq += 1;
+
+    Some(StockDatagram::Delta {
+      seq: self.seq,
+      sent_at: Some(unix_millis()),
+      quotes: changed,
+    })
+  }
+}
+
+#[cfg(test)]
+mod tests {
+  use std::thread;
+
+  use common::stock::QuoteField;
+
+  use super::*;
+  use crate::quote::QuoteGenerator;
+
+  fn quote(price: f64, volume: u32, timestamp: u64) -> StockQuote {
+    StockQuote {
+      ticker: "AAPL".to_string(),
+      price: Some(price),
+      volume: Some(volume),
+      timestamp: Some(timestamp),
+    }
+  }
+
+  fn delta_subscription(options: SubscriptionOptions) -> Subscription {
+    let tickers = vec!["AAPL".to_string()];
+    let filter =
+      TickerFilter::compile(&tickers, &tickers, &HashMap::new()).unwrap();
+
+    Subscription::new(
+      filter,
+      &SubscriptionOptions {
+        delta: true,
+        ..options
+      },
+      Duration::from_secs(1),
+    )
+  }
+
+  fn with_fields(fields: Vec<QuoteField>) -> SubscriptionOptions {
+    SubscriptionOptions {
+      fields: Some(fields),
+      ..SubscriptionOptions::default()
+    }
+  }
+
+  #[test]
+  fn price_and_volume_changes_are_sent() {
+    let mut subscription = delta_subscription(SubscriptionOptions::default());
+
+    subscription.push(&[quote(1.0, 100, 1)]);
+    assert!(matches!(
+      subscription.take_due(Instant::now()),
+      Some(StockDatagram::Snapshot { refresh: false, .. })
+    ));
+
+    subscription.push(&[quote(1.0, 100, 2)]);
+    assert!(subscription.take_due(Instant::now()).is_none());
+
+    subscription.push(&[quote(2.0, 100, 3)]);
+    assert!(matches!(
+      subscription.take_due(Instant::now()),
+      Some(StockDatagram::Delta { quotes, .. }) if quotes[0].timestamp == Some(3)
+    ));
+
+    subscription.push(&[quote(2.0, 200, 4)]);
+    assert!(subscription.take_due(Instant::now()).is_some());
+  }
+
+  #[test]
+  fn generated_quotes_are_sent_on_price_changes() {
+    let mut generator = QuoteGenerator::new(&["AAPL".to_string()]);
+    let mut subscription = delta_subscription(with_fields(vec![
+      QuoteField::Price,
+      QuoteField::Timestamp,
+    ]));
+
+    subscription.push(&generator.generate_quote_list());
+    assert!(subscription.take_due(Instant::now()).is_some());
+
+    // Generated quotes are stamped anew, volume is not requested
+    thread::sleep(Duration::from_millis(2));
+    subscription.push(&generator.generate_quote_list());
+    assert!(subscription.take_due(Instant::now()).is_none());
+
+    generator.shuffle_prices();
+    subscription.push(&generator.generate_quote_list());
+    assert!(subscription.take_due(Instant::now()).is_some());
+  }
+
+  #[test]
+  fn other_field_changes_are_not_sent() {
+    let mut subscription =
+      delta_subscription(with_fields(vec![QuoteField::Volume]));
+
+    subscription.push(&[quote(1.0, 100, 1)]);
+    assert!(subscription.take_due(Instant::now()).is_some());
+
+    subscription.push(&[quote(2.0, 100, 2)]);
+    assert!(subscription.take_due(Instant::now()).is_none());
+  }
+
+  #[test]
+  fn refresh_resends_sent_quotes() {
+    let mut subscription = delta_subscription(SubscriptionOptions {
+      full_refresh_ms: Some(0),
+      ..SubscriptionOptions::default()
+    });
+
+    subscription.push(&[quote(1.0, 100, 1)]);
+    assert!(subscription.take_due(Instant::now()).is_some());
+
+    subscription.push(&[quote(1.0, 100, 2)]);
+    assert!(matches!(
+      subscription.take_due(Instant::now()),
+      Some(StockDatagram::Snapshot { refresh: true, quotes, .. })
+        if quotes == [quote(1.0, 100, 1)]
+    ));
+  }
+}
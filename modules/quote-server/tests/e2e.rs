@@ -0,0 +1,421 @@
+use std::{
+  collections::{HashMap, HashSet},
+  env, fs,
+  io::{BufReader, Read, Write},
+  net::{TcpListener, TcpStream},
+  process, thread,
+  time::{Duration, Instant},
+};
+
+use common::{
+  error::AppError,
+  stock::{
+    QuoteField, StockDatagram, StockResponse, StockResponseStatus,
+    SubscriptionOptions,
+  },
+  utils::read_json,
+};
+use quote_client::{Subscription, SubscriptionConfig};
+use quote_server::{AccessPolicy, Limits, ServerHandle, UserStore, consts};
+use test_support::{
+  HEALTHCHECK_TIMEOUT, RECV_TIMEOUT, RawSubscriber, send_raw, start, subscribe,
+  subscribe_with, test_server,
+};
+
+/// PBKDF2-HMAC-SHA256 of `bob-password` with `salt` and 1000 iterations
+const BOB_HASH: &str =
+  "70982565cb473251382d178f0b44eb9f87fdfd82ed35e98914b7bbebe9bb57e9";
+
+/// Server authenticating `bob`, who is entitled to `entitlements`
+fn authenticated_server(
+  name: &str,
+  entitlements: &[&str],
+) -> Result<ServerHandle, AppError> {
+  let credentials = env::temp_dir()
+    .join(format!("quote-credentials-{name}-{}.txt", process::id()));
+  fs::write(&credentials, format!("bob:1000:salt:{BOB_HASH}\n")).unwrap();
+  let users = UserStore::read(credentials.clone()).unwrap();
+  fs::remove_file(&credentials).unwrap();
+
+  test_server()
+    .policy(AccessPolicy {
+      users: Some(users),
+      entitlements: Some(HashMap::from([(
+        "bob".to_string(),
+        entitlements.iter().map(ToString::to_string).collect(),
+      )])),
+      ..AccessPolicy::default()
+    })
+    .spawn()
+}
+
+#[test]
+fn subscription_is_filtered() {
+  let server = test_server().spawn().unwrap();
+  let subscription = subscribe(&server, &["AAPL", "G*"]);
+
+  let tickers: HashSet<String> = (0..20)
+    .map(|_| subscription.recv_timeout(RECV_TIMEOUT).unwrap().ticker)
+    .collect();
+
+  assert_eq!(tickers, HashSet::from(["AAPL".into(), "GOOGL".into()]));
+}
+
+#[test]
+fn clients_receive_own_tickers() {
+  let server = test_server().spawn().unwrap();
+  let subscriptions: Vec<_> = ["AAPL", "MSFT", "TSLA", "JPM"]
+    .into_iter()
+    .map(|ticker| (ticker, subscribe(&server, &[ticker])))
+    .collect();
+
+  for (ticker, subscription) in &subscriptions {
+    for _ in 0..5 {
+      let quote = subscription.recv_timeout(RECV_TIMEOUT).unwrap();
+      assert_eq!(quote.ticker, *ticker);
+    }
+  }
+}
+
+/// Timestamps of quotes received within a second, quotes are stamped with
+/// the tick number of test servers
+fn received_timestamps(subscription: &Subscription) -> Vec<u64> {
+  let deadline = Instant::now() + Duration::from_secs(1);
+  let mut timestamps = vec![];
+
+  while let Some(quote) = subscription
+    .recv_timeout(deadline.saturating_duration_since(Instant::now()))
+  {
+    timestamps.push(quote.timestamp.unwrap());
+  }
+
+  timestamps
+}
+
+#[test]
+fn updates_are_throttled_and_conflated() {
+  let server = test_server().spawn().unwrap();
+  // Stricter limit applies, 200ms of 20ms ticks
+  let options = [
+    SubscriptionOptions {
+      min_interval_ms: Some(200),
+      ..SubscriptionOptions::default()
+    },
+    SubscriptionOptions {
+      min_interval_ms: Some(50),
+      max_rate: Some(5.0),
+      ..SubscriptionOptions::default()
+    },
+  ];
+
+  for options in options {
+    let subscription = subscribe_with(&server, &["AAPL"], options);
+    let timestamps = received_timestamps(&subscription);
+
+    assert!((2..=7).contains(&timestamps.len()), "{timestamps:?}");
+    // Latest quote is sent, ticks in between are conflated
+    for pair in timestamps.windows(2) {
+      assert!(pair[1] - pair[0] >= 5, "{timestamps:?}");
+    }
+  }
+}
+
+#[test]
+fn projected_quotes_are_decoded() {
+  let server = test_server().spawn().unwrap();
+  let subscription = subscribe_with(
+    &server,
+    &["AAPL"],
+    SubscriptionOptions {
+      fields: Some(vec![QuoteField::Price]),
+      ..SubscriptionOptions::default()
+    },
+  );
+
+  for _ in 0..3 {
+    let quote = subscription.recv_timeout(RECV_TIMEOUT).unwrap();
+    assert_eq!(quote.ticker, "AAPL");
+    assert!(quote.price.is_some());
+    assert_eq!((quote.volume, quote.timestamp), (None, None));
+  }
+}
+
+#[test]
+fn users_are_authenticated_and_entitled() {
+  let server = authenticated_server("entitled", &["TECH"]).unwrap();
+  let request = |username: &str, password: &str| {
+    format!(
+      r#"{{"kind":"STREAM","addr":"127.0.0.1:9","tickers":["*"],"auth":{{"username":"{username}","password":"{password}"}}}}"#
+    )
+  };
+
+  let response = send_raw(
+    server.local_addrs().tcp,
+    request("bob", "bob-password").as_bytes(),
+  );
+  assert!(matches!(response.status, StockResponseStatus::Ok));
+  let report = response.report.unwrap();
+  let mut accepted = report.accepted.clone();
+  accepted.sort();
+  assert_eq!(accepted, vec!["AAPL", "GOOGL", "MSFT"]);
+  assert_eq!(report.denied, vec!["JPM", "TSLA"]);
+
+  for (username, password) in [("bob", "wrong-password"), ("eve", "")] {
+    let response = send_raw(
+      server.local_addrs().tcp,
+      request(username, password).as_bytes(),
+    );
+    assert!(matches!(response.status, StockResponseStatus::Error));
+    assert_eq!(response.message, "Invalid credentials");
+  }
+}
+
+#[test]
+fn invalid_entitlements_fail_start() {
+  assert!(matches!(
+    authenticated_server("invalid", &["/[/"]),
+    Err(AppError::OtherError(_))
+  ));
+}
+
+#[test]
+fn group_subscription_is_resolved() {
+  let server = test_server().spawn().unwrap();
+  let subscriber = RawSubscriber::subscribe(&server, &["TECH"]);
+
+  let report = subscriber.response.report.as_ref().unwrap();
+  let mut accepted = report.accepted.clone();
+  accepted.sort();
+
+  assert_eq!(accepted, vec!["AAPL", "GOOGL", "MSFT"]);
+}
+
+#[test]
+fn strict_subscription_with_unknown_tickers_is_rejected() {
+  let server = test_server().spawn().unwrap();
+  let subscription = subscribe_with(
+    &server,
+    &["AAPL", "NOPE"],
+    SubscriptionOptions {
+      strict: true,
+      ..SubscriptionOptions::default()
+    },
+  );
+
+  assert!(subscription.recv_timeout(RECV_TIMEOUT).is_none());
+  assert!(matches!(
+    subscription.join(),
+    Err(AppError::SubscriptionRejected { message }) if message.contains("NOPE")
+  ));
+}
+
+#[test]
+fn accepted_response_without_session_is_protocol_error() {
+  let listener = TcpListener::bind("127.0.0.1:0").unwrap();
+  let tcp = listener.local_addr().unwrap();
+  let server = thread::spawn(move || {
+    let (mut stream, _) = listener.accept().unwrap();
+    assert!(stream.read(&mut [0u8; 1024]).unwrap() > 0);
+    stream
+      .write_all(br#"{"status":"Ok","message":"Streaming"}"#)
+      .unwrap();
+  });
+
+  let subscription = start(SubscriptionConfig::new(
+    tcp,
+    9,
+    "127.0.0.1:0".parse().unwrap(),
+    vec!["AAPL".to_string()],
+  ));
+
+  assert!(subscription.recv_timeout(RECV_TIMEOUT).is_none());
+  assert!(matches!(
+    subscription.join(),
+    Err(AppError::ProtocolError { .. })
+  ));
+  server.join().unwrap();
+}
+
+#[test]
+fn unsupported_command_is_rejected() {
+  let server = test_server().spawn().unwrap();
+
+  let response = send_raw(
+    server.local_addrs().tcp,
+    br#"{"kind":"QUOTE","addr":"127.0.0.1:9","tickers":["AAPL"]}"#,
+  );
+
+  assert!(matches!(response.status, StockResponseStatus::Error));
+  assert_eq!(response.message, "Unsupported command");
+  assert!(response.session.is_none());
+}
+
+#[test]
+fn malformed_request_is_rejected() {
+  let server = test_server().spawn().unwrap();
+
+  for message in [&b"not json"[..], br#"{"kind":"STREAM"}"#, b"[1, 2"] {
+    let response = send_raw(server.local_addrs().tcp, message);
+
+    assert!(matches!(response.status, StockResponseStatus::Error));
+    assert_eq!(response.message, "Malformed request");
+  }
+
+  // Listener keeps serving other clients
+  let subscription = subscribe(&server, &["AAPL"]);
+  assert!(subscription.recv_timeout(RECV_TIMEOUT).is_some());
+}
+
+#[test]
+fn oversized_request_is_cut_short() {
+  let server = test_server().spawn().unwrap();
+  let mut stream = TcpStream::connect(server.local_addrs().tcp).unwrap();
+  stream.set_read_timeout(Some(RECV_TIMEOUT)).unwrap();
+
+  // Unterminated request of exactly the limit is answered without waiting
+  // for the rest
+  let mut message = br#"{"kind":"STREAM","tickers":[""#.to_vec();
+  message.resize(consts::MAX_REQUEST_BYTES as usize, b'A');
+  stream.write_all(&message).unwrap();
+
+  let response = read_json::<StockResponse>(BufReader::new(&mut stream));
+  assert_eq!(response.unwrap().message, "Malformed request");
+}
+
+#[test]
+fn trickling_client_is_cut_off_at_handshake_deadline() {
+  let server = test_server().spawn().unwrap();
+  let mut stream = TcpStream::connect(server.local_addrs().tcp).unwrap();
+  let started = Instant::now();
+
+  // A byte within every read timeout used to hold the handshake forever
+  let mut trickle = stream.try_clone().unwrap();
+  thread::spawn(move || {
+    while trickle.write_all(b" ").is_ok() {
+      thread::sleep(Duration::from_millis(200));
+    }
+  });
+
+  stream
+    .set_read_timeout(Some(consts::HANDSHAKE_TIMEOUT * 2))
+    .unwrap();
+  let _ = stream.read(&mut [0u8; 64]);
+  assert!(started.elapsed() < consts::HANDSHAKE_TIMEOUT + RECV_TIMEOUT);
+
+  let subscription = subscribe(&server, &["AAPL"]);
+  assert!(subscription.recv_timeout(RECV_TIMEOUT).is_some());
+}
+
+#[test]
+fn pending_handshake_does_not_block_other_clients() {
+  let server = test_server().spawn().unwrap();
+  let _silent = TcpStream::connect(server.local_addrs().tcp).unwrap();
+  let started = Instant::now();
+
+  let subscriber = RawSubscriber::subscribe(&server, &["AAPL"]);
+
+  assert!(subscriber.recv(RECV_TIMEOUT).is_some());
+  assert!(started.elapsed() < consts::HANDSHAKE_TIMEOUT);
+}
+
+#[test]
+fn subscriptions_are_capped_per_client_ip() {
+  let server = test_server()
+    .policy(AccessPolicy {
+      limits: Limits {
+        max_subscriptions_per_ip: 2,
+        ..Limits::default()
+      },
+      ..AccessPolicy::default()
+    })
+    .spawn()
+    .unwrap();
+  let request = |addr: &str| {
+    format!(r#"{{"kind":"STREAM","addr":"{addr}","tickers":["AAPL"]}}"#)
+  };
+
+  // UDP addresses of other IPs are counted against the requesting client
+  for addr in ["127.0.0.2:9", "127.0.0.3:9"] {
+    let response = send_raw(server.local_addrs().tcp, request(addr).as_bytes());
+    assert!(matches!(response.status, StockResponseStatus::Ok));
+  }
+  let response =
+    send_raw(server.local_addrs().tcp, request("127.0.0.4:9").as_bytes());
+
+  assert!(matches!(response.status, StockResponseStatus::Retry));
+  assert_eq!(response.message, "Too many subscriptions from client IP");
+}
+
+#[test]
+fn refused_client_retries_until_subscribed() {
+  let server = test_server()
+    .policy(AccessPolicy {
+      limits: Limits {
+        max_subscribers: 1,
+        ..Limits::default()
+      },
+      ..AccessPolicy::default()
+    })
+    .spawn()
+    .unwrap();
+  // Holds the only slot until evicted without heartbeats
+  let _subscriber = RawSubscriber::subscribe(&server, &["AAPL"]);
+
+  let subscription = subscribe(&server, &["MSFT"]);
+
+  let quote = subscription.recv_timeout(RECV_TIMEOUT * 2).unwrap();
+  assert_eq!(quote.ticker, "MSFT");
+}
+
+#[test]
+fn silent_client_is_evicted() {
+  let server = test_server().spawn().unwrap();
+  let subscriber = RawSubscriber::subscribe(&server, &["AAPL"]);
+
+  assert!(
+    subscriber
+      .recv_until(RECV_TIMEOUT, |datagram| !datagram.quotes().is_empty())
+      .is_some()
+  );
+
+  // No heartbeats are sent, so streaming stops after the timeout
+  thread::sleep(HEALTHCHECK_TIMEOUT * 2);
+  subscriber.drain();
+
+  assert!(subscriber.recv(HEALTHCHECK_TIMEOUT).is_none());
+}
+
+#[test]
+fn heartbeating_client_is_not_evicted() {
+  let server = test_server().spawn().unwrap();
+  let mut subscriber = RawSubscriber::subscribe(&server, &["AAPL"]);
+
+  for _ in 0..(HEALTHCHECK_TIMEOUT * 3).as_millis() / 50 {
+    subscriber.heartbeat();
+    thread::sleep(Duration::from_millis(50));
+  }
+  subscriber.drain();
+
+  assert!(
+    subscriber
+      .recv_until(RECV_TIMEOUT, |datagram| !datagram.quotes().is_empty())
+      .is_some()
+  );
+}
+
+#[test]
+fn subscribers_are_notified_on_shutdown() {
+  let server = test_server().spawn().unwrap();
+  let mut subscriber = RawSubscriber::subscribe(&server, &["AAPL"]);
+  subscriber.heartbeat();
+  assert!(subscriber.recv(RECV_TIMEOUT).is_some());
+
+  server.shutdown().unwrap();
+
+  assert!(matches!(
+    subscriber.recv_until(RECV_TIMEOUT, |datagram| {
+      matches!(datagram, StockDatagram::GoingAway { .. })
+    }),
+    Some(StockDatagram::GoingAway { .. })
+  ));
+}
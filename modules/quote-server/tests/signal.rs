@@ -0,0 +1,34 @@
+use std::{
+  net::TcpStream,
+  sync::{Arc, atomic::AtomicBool},
+};
+
+use signal_hook::{consts::SIGTERM, low_level::raise};
+
+use common::{stock::StockDatagram, utils::register_signal_hooks};
+use test_support::{RECV_TIMEOUT, RawSubscriber, test_server};
+
+// Signal hooks are process wide, so this binary holds a single test
+#[test]
+fn termination_signal_shuts_down_server() {
+  let shutdown = Arc::new(AtomicBool::new(false));
+  register_signal_hooks(&shutdown).unwrap();
+
+  let server = test_server().shutdown(shutdown).spawn().unwrap();
+  let tcp = server.local_addrs().tcp;
+  let mut subscriber = RawSubscriber::subscribe(&server, &["*"]);
+  subscriber.heartbeat();
+  assert!(subscriber.recv(RECV_TIMEOUT).is_some());
+
+  raise(SIGTERM).unwrap();
+  server.join().unwrap();
+
+  assert!(
+    subscriber
+      .recv_until(RECV_TIMEOUT, |datagram| {
+        matches!(datagram, StockDatagram::GoingAway { .. })
+      })
+      .is_some()
+  );
+  assert!(TcpStream::connect(tcp).is_err());
+}
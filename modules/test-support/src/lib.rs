@@ -0,0 +1,234 @@
+//! Fixtures of the workspace integration tests: servers on ephemeral loopback
+//! ports with short timings, library and raw protocol subscribers.
+
+use std::{
+  collections::HashMap,
+  io::{BufReader, Write},
+  net::{Shutdown, SocketAddr, TcpStream, UdpSocket},
+  sync::{Arc, atomic::AtomicBool},
+  time::{Duration, Instant},
+};
+
+use serde_json::json;
+
+use common::{
+  session::{ClientDatagram, Heartbeat, SessionCredentials},
+  stock::{
+    StockDatagram, StockQuote, StockRequest, StockResponse,
+    StockResponseStatus, SubscriptionOptions,
+  },
+  utils::read_json,
+};
+use quote_client::{Subscription, SubscriptionConfig};
+use quote_server::{
+  AccessPolicy, Limits, LocalAddrs, ServerBuilder, ServerHandle, Timings,
+};
+
+pub const TICKERS: [&str; 5] = ["AAPL", "GOOGL", "MSFT", "TSLA", "JPM"];
+/// Eviction timeout without heartbeats of test servers
+pub const HEALTHCHECK_TIMEOUT: Duration = Duration::from_millis(300);
+pub const RECV_TIMEOUT: Duration = Duration::from_secs(2);
+
+/// Short server timings, so each test runs in a fraction of a second
+pub fn test_timings() -> Timings {
+  Timings {
+    quotes_interval: Duration::from_millis(20),
+    heartbeat_interval: Duration::from_millis(50),
+    healthcheck_timeout: HEALTHCHECK_TIMEOUT,
+    healthcheck_monitor_interval: Duration::from_millis(10),
+    ownership_verification_timeout: Duration::from_secs(1),
+    challenge_retry_interval: Duration::from_millis(100),
+    server_heartbeat_interval: Duration::from_millis(100),
+    shutdown_drain_timeout: Duration::from_secs(1),
+  }
+}
+
+/// Server of `TICKERS` with `TECH` group on ephemeral loopback ports
+pub fn test_server() -> ServerBuilder {
+  let groups = HashMap::from([(
+    "TECH".to_string(),
+    vec!["AAPL".to_string(), "GOOGL".to_string(), "MSFT".to_string()],
+  )]);
+  let mut tick = 0;
+
+  ServerBuilder::new(TICKERS.map(String::from).to_vec())
+    .tcp_addr("127.0.0.1:0".parse().unwrap())
+    .udp_addr("127.0.0.1:0".parse().unwrap())
+    .groups(groups)
+    .policy(AccessPolicy {
+      limits: Limits {
+        min_heartbeat_interval: Duration::from_millis(10),
+        ..Limits::default()
+      },
+      ..AccessPolicy::default()
+    })
+    .timings(test_timings())
+    .quote_source(move || {
+      tick += 1;
+      TICKERS
+        .iter()
+        .map(|ticker| StockQuote {
+          ticker: ticker.to_string(),
+          price: Some(tick as f64),
+          volume: Some(100),
+          timestamp: Some(tick),
+        })
+        .collect()
+    })
+}
+
+pub fn subscribe(server: &ServerHandle, tickers: &[&str]) -> Subscription {
+  subscribe_with(server, tickers, SubscriptionOptions::default())
+}
+
+pub fn subscribe_with(
+  server: &ServerHandle,
+  tickers: &[&str],
+  options: SubscriptionOptions,
+) -> Subscription {
+  start(SubscriptionConfig {
+    options,
+    ..subscription_config(server, tickers)
+  })
+}
+
+/// Config of subscription to `server` on an ephemeral loopback port
+pub fn subscription_config(
+  server: &ServerHandle,
+  tickers: &[&str],
+) -> SubscriptionConfig {
+  let LocalAddrs { tcp, udp } = server.local_addrs();
+
+  SubscriptionConfig::new(
+    tcp,
+    udp.port(),
+    "127.0.0.1:0".parse().unwrap(),
+    tickers.iter().map(|ticker| ticker.to_string()).collect(),
+  )
+}
+
+pub fn start(config: SubscriptionConfig) -> Subscription {
+  Subscription::start(config, Arc::new(AtomicBool::new(false))).unwrap()
+}
+
+/// Send raw bytes as a control request and read the response
+pub fn send_raw(tcp: SocketAddr, message: &[u8]) -> StockResponse {
+  let mut stream = TcpStream::connect(tcp).unwrap();
+  stream.set_read_timeout(Some(RECV_TIMEOUT)).unwrap();
+  stream.write_all(message).unwrap();
+  stream.shutdown(Shutdown::Write).unwrap();
+
+  read_json::<StockResponse>(BufReader::new(&mut stream)).unwrap()
+}
+
+/// Subscriber speaking the wire protocol directly, heartbeats are sent
+/// explicitly only
+pub struct RawSubscriber {
+  pub udp: UdpSocket,
+  pub response: StockResponse,
+  server_udp: SocketAddr,
+  session: SessionCredentials,
+  counter: u64,
+}
+
+impl RawSubscriber {
+  /// Subscribe and verify the UDP address
+  pub fn subscribe(server: &ServerHandle, tickers: &[&str]) -> Self {
+    let LocalAddrs {
+      tcp,
+      udp: server_udp,
+    } = server.local_addrs();
+    let udp = UdpSocket::bind("127.0.0.1:0").unwrap();
+    udp
+      .set_read_timeout(Some(Duration::from_millis(50)))
+      .unwrap();
+
+    let request = StockRequest {
+      kind: "STREAM".to_string(),
+      addr: udp.local_addr().unwrap(),
+      tickers: tickers.iter().map(|ticker| ticker.to_string()).collect(),
+      options: SubscriptionOptions::default(),
+      auth: None,
+    };
+    let response = send_raw(tcp, json!(request).to_string().as_bytes());
+    assert!(
+      matches!(response.status, StockResponseStatus::Ok),
+      "{}",
+      response.message
+    );
+
+    let mut subscriber = Self {
+      udp,
+      server_udp,
+      session: response.session.clone().unwrap(),
+      response,
+      counter: 0,
+    };
+    subscriber.verify();
+
+    subscriber
+  }
+  fn verify(&mut self) {
+    let Some(StockDatagram::Challenge { nonce }) = self
+      .recv_until(RECV_TIMEOUT, |datagram| {
+        matches!(datagram, StockDatagram::Challenge { .. })
+      })
+    else {
+      panic!("Challenge is not received");
+    };
+
+    self.send(&ClientDatagram::Verification { nonce });
+  }
+  pub fn heartbeat(&mut self) {
+    self.counter += 1;
+    let heartbeat = Heartbeat::sign(&self.session, self.counter).unwrap();
+
+    self.send(&ClientDatagram::Heartbeat(heartbeat));
+  }
+  fn send(&self, datagram: &ClientDatagram) {
+    self
+      .udp
+      .send_to(json!(datagram).to_string().as_bytes(), self.server_udp)
+      .unwrap();
+  }
+  /// Discard datagrams already received, returns their count
+  pub fn drain(&self) -> usize {
+    let mut buf = vec![0u8; 64 * 1024];
+    let mut drained = 0;
+
+    self.udp.set_nonblocking(true).unwrap();
+    while self.udp.recv(&mut buf).is_ok() {
+      drained += 1;
+    }
+    self.udp.set_nonblocking(false).unwrap();
+
+    drained
+  }
+  /// Next datagram received within `timeout`
+  pub fn recv(&self, timeout: Duration) -> Option<StockDatagram> {
+    self.recv_until(timeout, |_| true)
+  }
+  /// First datagram matching `accept` received within `timeout`
+  pub fn recv_until(
+    &self,
+    timeout: Duration,
+    accept: impl Fn(&StockDatagram) -> bool,
+  ) -> Option<StockDatagram> {
+    let deadline = Instant::now() + timeout;
+    let mut buf = vec![0u8; 64 * 1024];
+
+    while Instant::now() < deadline {
+      let Ok(n) = self.udp.recv(&mut buf) else {
+        continue;
+      };
+      let datagram =
+        serde_json::from_slice::<StockDatagram>(&buf[..n]).unwrap();
+
+      if accept(&datagram) {
+        return Some(datagram);
+      }
+    }
+
+    None
+  }
+}